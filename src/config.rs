@@ -0,0 +1,39 @@
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+
+// under the user's config and data dirs, shared by every game
+const APP_DIR: &str = "bevytuts";
+
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR).join(file))
+}
+
+pub fn data_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_DIR).join(file))
+}
+
+// a RON file from the config dir, the defaults when there is none or it can't be read
+pub fn load_config<T: DeserializeOwned + Default>(file: &str) -> T {
+    let path = match config_path(file) {
+        Some(path) => path,
+        None => return T::default(),
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return T::default(),
+    };
+
+    match ron::from_str(&contents) {
+        Ok(config) => {
+            info!("Loaded {}", path.display());
+            config
+        }
+        Err(e) => {
+            warn!("Ignoring malformed {}: {}", path.display(), e);
+            T::default()
+        }
+    }
+}
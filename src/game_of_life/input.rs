@@ -1,5 +1,7 @@
 use bevy::{prelude::*, core::FixedTimestep};
 
+use crate::input_map::{Action, ActionState};
+
 const CAMERA_MOVE_SPEED: f32 = 15.0;
 const CAMERA_ZOOM_SPEED: f32 = 1.0;
 
@@ -7,8 +9,8 @@ const CAMERA_ZOOM_SPEED: f32 = 1.0;
 pub struct MainCamera;
 
 #[derive(Component)]
-struct Movement {
-    plane_speed: Vec3,
+pub struct Movement {
+    pub plane_speed: Vec3,
     zoom_speed: f32,
 }
 
@@ -39,22 +41,22 @@ fn setup(mut commands: Commands) {
 
 fn camera_move(
     mut camera: Query<(&mut Transform, &mut Movement), With<MainCamera>>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
     let mut move_direction = Vec3::new(0.0, 0.0, 0.0);
-    if keyboard_input.pressed(KeyCode::W) {
+    if actions.pressed(Action::PanUp) {
         move_direction.y += 1.0;
     }
     
-    if keyboard_input.pressed(KeyCode::S) {
+    if actions.pressed(Action::PanDown) {
         move_direction.y -= 1.0;
     }
     
-    if keyboard_input.pressed(KeyCode::A) {
+    if actions.pressed(Action::PanLeft) {
         move_direction.x -= 1.0;
     }
     
-    if keyboard_input.pressed(KeyCode::D) {
+    if actions.pressed(Action::PanRight) {
         move_direction.x += 1.0;
     }
     let move_direction = move_direction.normalize_or_zero();
@@ -64,7 +66,7 @@ fn camera_move(
                                 .clamp(Vec3::new(-CAMERA_MOVE_SPEED, -CAMERA_MOVE_SPEED, -CAMERA_MOVE_SPEED),
                                 Vec3::new(CAMERA_MOVE_SPEED, CAMERA_MOVE_SPEED, CAMERA_MOVE_SPEED));
 
-    if keyboard_input.pressed(KeyCode::Space) {
+    if actions.pressed(Action::CameraStop) {
         movement.plane_speed = Vec3::new(0.0, 0.0, 0.0);
     }
 
@@ -73,14 +75,14 @@ fn camera_move(
 
 fn camera_zoom(
     mut camera: Query<(&mut Movement, &mut OrthographicProjection), With<MainCamera>>,
-    keyboard_input: Res<Input<KeyCode>>
+    actions: Res<ActionState>,
 ) {
     let mut zoom_direction = 0.0;
 
-    if keyboard_input.pressed(KeyCode::Q) {
+    if actions.pressed(Action::ZoomOut) {
         zoom_direction = 0.01;
     }
-    if keyboard_input.pressed(KeyCode::E) {
+    if actions.pressed(Action::ZoomIn) {
         zoom_direction = -0.01;
     }
 
@@ -89,7 +91,7 @@ fn camera_zoom(
     movement.zoom_speed = (movement.zoom_speed + zoom_direction).clamp(-CAMERA_ZOOM_SPEED, CAMERA_ZOOM_SPEED);
     orto_proj.scale = (orto_proj.scale + movement.zoom_speed).clamp(1.0, 6.0);
 
-    if (orto_proj.scale -1.0).abs() < 0.001 || (orto_proj.scale - 6.0).abs() < 0.0001 || keyboard_input.pressed(KeyCode::Space) {
+    if (orto_proj.scale -1.0).abs() < 0.001 || (orto_proj.scale - 6.0).abs() < 0.0001 || actions.pressed(Action::CameraStop) {
         movement.zoom_speed = 0.0;
     }
 }
\ No newline at end of file
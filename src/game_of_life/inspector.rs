@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+
+use super::input::MainCamera;
+use super::simulation::{
+    count_neighbours,
+    get_mouse_world,
+    life_grid,
+    BoardSettings,
+    Cell,
+    CellState,
+};
+use super::theme::CurrentTheme;
+use crate::input_map::{Action, ActionState};
+
+const GRID_LINE_WIDTH: f32 = 1.0;
+const GRID_LINE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const AXIS_LABEL_STEP: i32 = 10;
+const AXIS_LABEL_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+
+#[derive(Component)]
+struct InspectorText;
+
+#[derive(Component)]
+struct GridOverlay;
+
+// Resource
+struct ShowGrid(bool);
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(ShowGrid(false))
+            .add_startup_system(setup)
+            .add_system(rebuild_grid_overlay)
+            .add_system(toggle_grid)
+            .add_system(inspect_hovered_cell);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+) {
+    // status readout in the top left corner
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..Default::default()
+                },
+                Default::default()
+            ),
+            ..Default::default()
+        })
+        .insert(InspectorText);
+}
+
+fn rebuild_grid_overlay(
+    mut commands: Commands,
+    settings: Res<BoardSettings>,
+    show_grid: Res<ShowGrid>,
+    current_theme: Option<Res<CurrentTheme>>,
+    overlay: Query<Entity, With<GridOverlay>>,
+    mut built_dimensions: Local<Option<(i32, f32)>>,
+) {
+    let dimensions = (settings.grid_size, settings.cell_size);
+    if *built_dimensions == Some(dimensions) {
+        return;
+    }
+    *built_dimensions = Some(dimensions);
+
+    for entity in overlay.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    // grid lines on the cell borders
+    let cell_size = settings.cell_size;
+    let board_start = -cell_size / 2.0;
+    let board_length = settings.grid_size as f32 * cell_size;
+    let board_center = board_start + board_length / 2.0;
+    for i in 0..=settings.grid_size {
+        let offset = board_start + i as f32 * cell_size;
+        for (translation, size) in [
+            (Vec3::new(offset, board_center, 1.0), Vec2::new(GRID_LINE_WIDTH, board_length)),
+            (Vec3::new(board_center, offset, 1.0), Vec2::new(board_length, GRID_LINE_WIDTH)),
+        ] {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: GRID_LINE_COLOR,
+                        custom_size: Some(size),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(translation),
+                    visibility: Visibility { is_visible: show_grid.0 },
+                    ..Default::default()
+                })
+                .insert(GridOverlay);
+        }
+    }
+
+    // axis labels below the first row and left of the first column
+    let mut label_style = TextStyle {
+        font_size: 24.0,
+        color: AXIS_LABEL_COLOR,
+        ..Default::default()
+    };
+    if let Some(current_theme) = current_theme {
+        label_style.font = current_theme.0.font.clone();
+        label_style.color = current_theme.0.text_color;
+        label_style.color.set_a(AXIS_LABEL_COLOR.a());
+    }
+    let label_alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    for i in (0..settings.grid_size).step_by(AXIS_LABEL_STEP as usize) {
+        let position = i as f32 * cell_size;
+        for translation in [
+            Vec3::new(position, -cell_size, 1.0),
+            Vec3::new(-cell_size, position, 1.0),
+        ] {
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(i.to_string(), label_style.clone(), label_alignment),
+                    transform: Transform::from_translation(translation),
+                    visibility: Visibility { is_visible: show_grid.0 },
+                    ..Default::default()
+                })
+                .insert(GridOverlay);
+        }
+    }
+}
+
+fn toggle_grid(
+    actions: Res<ActionState>,
+    mut show_grid: ResMut<ShowGrid>,
+    mut overlay: Query<&mut Visibility, With<GridOverlay>>,
+) {
+    if !actions.just_pressed(Action::ToggleGrid) {
+        return;
+    }
+
+    show_grid.0 = !show_grid.0;
+    for mut visibility in overlay.iter_mut() {
+        visibility.is_visible = show_grid.0;
+    }
+}
+
+fn inspect_hovered_cell(
+    windows: Res<Windows>,
+    main_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    cells: Query<&Cell>,
+    settings: Res<BoardSettings>,
+    mut inspector_text: Query<&mut Text, With<InspectorText>>,
+) {
+    let window = windows.get_primary().unwrap();
+    let mut text = inspector_text.single_mut();
+
+    let hovered = window.cursor_position().and_then(|pos| {
+        let (transform, proj) = main_camera.single();
+        let (x, y) = settings.world_to_grid(get_mouse_world(pos, transform, window, proj).truncate());
+        if settings.contains(x, y) {
+            Some((x, y))
+        } else {
+            None
+        }
+    });
+
+    let (x, y) = match hovered {
+        Some(xy) => xy,
+        None => {
+            text.sections[0].value.clear();
+            return;
+        }
+    };
+
+    let hovered_state = cells
+        .iter()
+        .find(|cell| (cell.x, cell.y) == (x, y))
+        .map_or(CellState::Empty, |cell| cell.state);
+    let life_grid = life_grid(cells.iter(), &settings);
+
+    let neighbour_count = count_neighbours(&life_grid, &settings, x, y);
+    text.sections[0].value = format!(
+        "({}, {}) {:?}  neighbours: {}  next: {:?} ({})",
+        x,
+        y,
+        hovered_state,
+        neighbour_count,
+        settings.rule.next_state(hovered_state, neighbour_count),
+        settings.rule,
+    );
+}
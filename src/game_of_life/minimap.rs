@@ -0,0 +1,211 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+    ui::FocusPolicy,
+};
+
+use super::input::{MainCamera, Movement};
+use super::simulation::{BoardSettings, Cell, CellState};
+
+const MINIMAP_SIZE: f32 = 160.0;
+const MINIMAP_MARGIN: f32 = 10.0;
+
+const ALIVE_PIXEL: [u8; 4] = [120, 255, 120, 255];
+const DEAD_PIXEL: [u8; 4] = [70, 70, 70, 255];
+const EMPTY_PIXEL: [u8; 4] = [15, 25, 15, 220];
+
+const VIEW_RECT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct MinimapViewRect;
+
+// Resource
+struct MinimapImage(Handle<Image>);
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(setup)
+            .add_system(resize_minimap.before(draw_minimap))
+            .add_system(draw_minimap)
+            .add_system(update_view_rect)
+            .add_system(minimap_navigation);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<BoardSettings>,
+) {
+    let image_handle = images.add(blank_minimap_image(settings.grid_size));
+
+    commands
+        .spawn_bundle(ImageBundle {
+            style: Style {
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(MINIMAP_MARGIN),
+                    bottom: Val::Px(MINIMAP_MARGIN),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            image: UiImage(image_handle.clone()),
+            ..Default::default()
+        })
+        .insert(Interaction::default())
+        .insert(Minimap)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..Default::default()
+                    },
+                    color: VIEW_RECT_COLOR.into(),
+                    focus_policy: FocusPolicy::Pass,
+                    ..Default::default()
+                })
+                .insert(MinimapViewRect);
+        });
+
+    commands.insert_resource(MinimapImage(image_handle));
+}
+
+// one pixel per cell
+fn blank_minimap_image(grid_size: i32) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: grid_size as u32,
+            height: grid_size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &EMPTY_PIXEL,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    // keep the cells crisp instead of blurring them together
+    image.sampler_descriptor.mag_filter = FilterMode::Nearest;
+    image.sampler_descriptor.min_filter = FilterMode::Nearest;
+    image
+}
+
+fn resize_minimap(
+    settings: Res<BoardSettings>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if let Some(image) = images.get_mut(&minimap_image.0) {
+        if image.texture_descriptor.size.width != settings.grid_size as u32 {
+            *image = blank_minimap_image(settings.grid_size);
+        }
+    }
+}
+
+fn draw_minimap(
+    cells: Query<&Cell, Changed<Cell>>,
+    settings: Res<BoardSettings>,
+    minimap_image: Res<MinimapImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if cells.is_empty() {
+        return;
+    }
+
+    let image = match images.get_mut(&minimap_image.0) {
+        Some(image) => image,
+        None => return,
+    };
+
+    let grid_size = settings.grid_size;
+    for cell in cells.iter() {
+        if !settings.contains(cell.x, cell.y) {
+            continue;
+        }
+
+        // image rows go from the top down, the grid goes from the bottom up
+        let pixel_index = ((grid_size - 1 - cell.y) * grid_size + cell.x) as usize * 4;
+        let pixel = match cell.state {
+            CellState::Alive => ALIVE_PIXEL,
+            CellState::Dead => DEAD_PIXEL,
+            CellState::Empty => EMPTY_PIXEL,
+        };
+        image.data[pixel_index..pixel_index + 4].copy_from_slice(&pixel);
+    }
+}
+
+fn update_view_rect(
+    windows: Res<Windows>,
+    main_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut view_rect: Query<&mut Style, With<MinimapViewRect>>,
+    settings: Res<BoardSettings>,
+) {
+    let window = windows.get_primary().unwrap();
+    let (transform, proj) = main_camera.single();
+
+    let center = transform.translation.truncate();
+    let half_extents = Vec2::new(window.width(), window.height()) * proj.scale / 2.0;
+    let min = world_to_minimap(center - half_extents, &settings);
+    let max = world_to_minimap(center + half_extents, &settings);
+
+    // clip the rectangle to the minimap
+    let min = min.max(Vec2::ZERO);
+    let max = max.min(Vec2::splat(MINIMAP_SIZE));
+    let size = (max - min).max(Vec2::ZERO);
+
+    for mut style in view_rect.iter_mut() {
+        style.position = Rect {
+            left: Val::Px(min.x),
+            top: Val::Px(min.y),
+            ..Default::default()
+        };
+        style.size = Size::new(Val::Px(size.x), Val::Px(size.y));
+    }
+}
+
+fn minimap_navigation(
+    windows: Res<Windows>,
+    minimap: Query<(&Interaction, &Node, &GlobalTransform), With<Minimap>>,
+    mut main_camera: Query<(&mut Transform, &mut Movement), With<MainCamera>>,
+    settings: Res<BoardSettings>,
+) {
+    let (interaction, node, global_transform) = minimap.single();
+    if *interaction != Interaction::Clicked {
+        return;
+    }
+
+    let cursor = match windows.get_primary().unwrap().cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // keep dragging along the border when the cursor leaves the minimap
+    let bottom_left = global_transform.translation.truncate() - node.size / 2.0;
+    let local = (cursor - bottom_left).clamp(Vec2::ZERO, node.size);
+    let world = minimap_to_world(local * MINIMAP_SIZE / node.size, &settings);
+
+    let (mut transform, mut movement) = main_camera.single_mut();
+    transform.translation.x = world.x;
+    transform.translation.y = world.y;
+    movement.plane_speed = Vec3::ZERO;
+}
+
+// cells are centered on multiples of the cell size, so the board starts half a cell left/below the origin
+fn world_to_minimap(world: Vec2, settings: &BoardSettings) -> Vec2 {
+    (world / settings.cell_size + Vec2::splat(0.5)) * MINIMAP_SIZE / settings.grid_size as f32
+}
+
+fn minimap_to_world(minimap: Vec2, settings: &BoardSettings) -> Vec2 {
+    (minimap * settings.grid_size as f32 / MINIMAP_SIZE - Vec2::splat(0.5)) * settings.cell_size
+}
@@ -1,13 +1,24 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, asset::AssetServerSettings};
 use input::InputPlugin;
+use inspector::InspectorPlugin;
+use minimap::MinimapPlugin;
+use settings::SettingsPlugin;
 use simulation::SimulationPlugin;
+use theme::ThemePlugin;
 use ui::MainMenuPlugin;
 
+use crate::input_map::InputMapPlugin;
+use crate::widgets::WidgetPlugin;
+
 const GRID_SIZE: i32 = 100;
 
 mod ui;
 mod input;
+mod inspector;
+mod minimap;
+mod settings;
 mod simulation;
+mod theme;
 
 pub fn game_of_life_app() {
     App::new()
@@ -18,9 +29,20 @@ pub fn game_of_life_app() {
             title: String::from("Game of Life"),
             ..Default::default()
         })
+        // hot reload themes and their textures
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
+            ..Default::default()
+        })
         .add_plugins(DefaultPlugins)
+        .add_plugin(InputMapPlugin)
+        .add_plugin(WidgetPlugin)
+        .add_plugin(ThemePlugin)
         .add_plugin(MainMenuPlugin)
         .add_plugin(InputPlugin)
         .add_plugin(SimulationPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(InspectorPlugin)
+        .add_plugin(SettingsPlugin)
         .run();
 }
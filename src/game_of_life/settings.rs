@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+
+use super::simulation::{BoardSettings, Rule, Topology};
+use super::ui::ThemedPanel;
+use crate::widgets::{
+    row_style, spawn_dropdown, spawn_label, spawn_slider, spawn_text_input, spawn_toggle,
+    spawn_button, Dropdown, Slider, TextInput, Toggle, WidgetAction, WidgetEvent, WidgetValue,
+};
+
+const PANEL_WIDTH: f32 = 240.0;
+const WIDGET_HEIGHT: f32 = 30.0;
+const FONT_SIZE: f32 = 18.0;
+
+const TOGGLE_PANEL: WidgetAction = WidgetAction("gol.settings.toggle");
+const GRID_SIZE: WidgetAction = WidgetAction("gol.settings.grid_size");
+const CELL_SIZE: WidgetAction = WidgetAction("gol.settings.cell_size");
+const SPEED: WidgetAction = WidgetAction("gol.settings.speed");
+const RULE: WidgetAction = WidgetAction("gol.settings.rule");
+const TOPOLOGY: WidgetAction = WidgetAction("gol.settings.topology");
+const APPLY: WidgetAction = WidgetAction("gol.settings.apply");
+
+const TOPOLOGIES: [(&str, Topology); 2] = [
+    ("Bounded", Topology::Bounded),
+    ("Toroidal", Topology::Toroidal),
+];
+
+#[derive(Component)]
+struct SettingsBody;
+
+#[derive(Component)]
+struct SettingsStatus;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(setup)
+            .add_system(toggle_settings_panel)
+            .add_system(apply_settings);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<BoardSettings>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(PANEL_WIDTH), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(180.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            let toggle = Toggle {
+                on: false,
+                off_label: "SETTINGS +".to_string(),
+                on_label: "SETTINGS -".to_string(),
+            };
+            spawn_toggle(parent, TOGGLE_PANEL, toggle, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        padding: Rect::all(Val::Px(4.0)),
+                        display: Display::None,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(SettingsBody)
+                .insert(ThemedPanel::Overlay)
+                // hovering the panel background counts as ui, so painting doesn't go through it
+                .insert(Interaction::default())
+                .with_children(|parent| {
+                    let grid_size = Slider {
+                        label: "Grid size".to_string(),
+                        min: 10.0,
+                        max: 200.0,
+                        step: 10.0,
+                        value: settings.grid_size as f32,
+                    };
+                    spawn_slider(parent, GRID_SIZE, grid_size, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    let cell_size = Slider {
+                        label: "Cell size".to_string(),
+                        min: 8.0,
+                        max: 64.0,
+                        step: 4.0,
+                        value: settings.cell_size,
+                    };
+                    spawn_slider(parent, CELL_SIZE, cell_size, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    let speed = Slider {
+                        label: "Generations/s".to_string(),
+                        min: 1.0,
+                        max: 30.0,
+                        step: 1.0,
+                        value: settings.generations_per_second,
+                    };
+                    spawn_slider(parent, SPEED, speed, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    let rule = TextInput {
+                        label: "Rule".to_string(),
+                        value: settings.rule.to_string(),
+                    };
+                    spawn_text_input(parent, RULE, rule, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    let topology = Dropdown {
+                        label: "Topology".to_string(),
+                        options: TOPOLOGIES.iter().map(|(name, _)| name.to_string()).collect(),
+                        selected: TOPOLOGIES
+                            .iter()
+                            .position(|(_, topology)| *topology == settings.topology)
+                            .unwrap_or(0),
+                    };
+                    spawn_dropdown(parent, TOPOLOGY, topology, row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    spawn_button(parent, APPLY, "APPLY", row_style(WIDGET_HEIGHT), FONT_SIZE);
+
+                    spawn_label(parent, "", FONT_SIZE)
+                        .insert(SettingsStatus);
+                });
+        });
+}
+
+fn toggle_settings_panel(
+    mut widget_events: EventReader<WidgetEvent>,
+    mut body: Query<&mut Style, With<SettingsBody>>,
+) {
+    for event in widget_events.iter() {
+        if let (TOGGLE_PANEL, WidgetValue::Toggled(open)) = (event.action, &event.value) {
+            for mut style in body.iter_mut() {
+                style.display = if *open { Display::Flex } else { Display::None };
+            }
+        }
+    }
+}
+
+fn apply_settings(
+    mut widget_events: EventReader<WidgetEvent>,
+    sliders: Query<(&Slider, &WidgetAction)>,
+    text_inputs: Query<(&TextInput, &WidgetAction)>,
+    dropdowns: Query<(&Dropdown, &WidgetAction)>,
+    mut status: Query<&mut Text, With<SettingsStatus>>,
+    mut settings: ResMut<BoardSettings>,
+) {
+    // the fields are only read when applying, so half-edited values never reach the board
+    if !widget_events.iter().any(|event| event.action == APPLY) {
+        return;
+    }
+
+    let mut new_settings = settings.clone();
+    for (slider, action) in sliders.iter() {
+        match *action {
+            GRID_SIZE => new_settings.grid_size = slider.value as i32,
+            CELL_SIZE => new_settings.cell_size = slider.value,
+            SPEED => new_settings.generations_per_second = slider.value,
+            _ => {}
+        }
+    }
+    for (dropdown, action) in dropdowns.iter() {
+        if *action == TOPOLOGY {
+            new_settings.topology = TOPOLOGIES[dropdown.selected].1;
+        }
+    }
+
+    let mut message = String::from("Applied");
+    for (text_input, action) in text_inputs.iter() {
+        if *action == RULE {
+            match Rule::parse(&text_input.value) {
+                Ok(rule) => new_settings.rule = rule,
+                Err(e) => message = format!("Bad rule: {}", e),
+            }
+        }
+    }
+
+    // only touch the settings when something changed, a rebuild is not free
+    if *settings != new_settings {
+        *settings = new_settings;
+    }
+    for mut text in status.iter_mut() {
+        text.sections[0].value = message.clone();
+    }
+}
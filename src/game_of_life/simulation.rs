@@ -1,23 +1,46 @@
+use std::{fmt, fs};
+
 use bevy::{prelude::*, core::FixedTimestep};
-use super::ui::{GameExitEvent, SimulationStartEvent, SimulationStopEvent};
+use rand::{thread_rng, Rng};
+use super::ui::{
+    BoardClearEvent,
+    BoardRandomizeEvent,
+    BoardSaveEvent,
+    GameExitEvent,
+    SimulationStepEvent,
+    SimulationToggleEvent,
+};
 use bevy::app::AppExit;
 use super::input::MainCamera;
+use super::theme::CurrentTheme;
+use crate::config::data_path;
+use crate::input_map::{Action, ActionState};
 
 const SPRITE_SIZE: f32 = 32.0;
+const GENERATIONS_PER_SECOND: f32 = 4.0;
+const RANDOM_ALIVE_CHANCE: f64 = 0.25;
+const SAVE_FILE: &str = "board.cells";
 
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(ClearColor(Color::rgb(0.01, 0.1, 0.001)))
             .insert_resource(MouseWorldPositionDraw(None))
             .insert_resource(MouseWorldPositionErase(None))
             .insert_resource(IsSimulationRunning(false))
-            .add_startup_system(setup)
+            .insert_resource(BoardSettings::default())
+            .insert_resource(SimulationTimer(Timer::from_seconds(1.0 / GENERATIONS_PER_SECOND, true)))
+            .insert_resource(SpriteImages::default())
+            .add_system(rebuild_board.before(CellInteraction::Input))
+            .add_system(apply_simulation_speed)
+            .add_system(apply_cell_theme.after(rebuild_board))
             .add_system(exit_game)
-            .add_system(set_simulation)
-            .add_system(unset_simulation)
+            .add_system(toggle_simulation)
+            .add_system(step_simulation.after(CellInteraction::Simulation))
+            .add_system(clear_board)
+            .add_system(randomize_board)
+            .add_system(save_board)
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::step(0.036))
@@ -30,14 +53,10 @@ impl Plugin for SimulationPlugin {
                             .after(CellInteraction::Input)
                     )
             )
-            .add_system_set(
-                SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(0.25))
-                    .with_system(
-                        simulation_step
-                            .label(CellInteraction::Simulation)
-                            .after(CellInteraction::Setting)
-                    )
+            .add_system(
+                simulation_step
+                    .label(CellInteraction::Simulation)
+                    .after(CellInteraction::Setting)
             );
     }
 }
@@ -48,11 +67,14 @@ struct MouseWorldPositionDraw(Option<(f32, f32)>);
 struct MouseWorldPositionErase(Option<(f32, f32)>);
 
 #[derive(Component)]
-struct Cell {
-    state: CellState,
+pub struct Cell {
+    pub state: CellState,
+    pub x: i32,
+    pub y: i32,
 }
 
-enum CellState {
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellState {
     Alive,
     Dead,
     Empty,
@@ -65,8 +87,131 @@ struct SpriteImages {
     dead_cell: Handle<Image>,
 }
 
+impl SpriteImages {
+    fn for_state(&self, state: CellState) -> Handle<Image> {
+        match state {
+            CellState::Alive => self.alive_cell.clone(),
+            CellState::Dead => self.dead_cell.clone(),
+            CellState::Empty => self.empty_cell.clone(),
+        }
+    }
+}
+
 #[derive(Default)]
-struct IsSimulationRunning(bool);
+pub struct IsSimulationRunning(pub bool);
+
+struct SimulationTimer(Timer);
+
+// region:      Settings
+// Resource, changing it rebuilds the board (size) or changes how it evolves (rule, topology, speed)
+#[derive(Clone, PartialEq)]
+pub struct BoardSettings {
+    pub grid_size: i32,
+    pub cell_size: f32,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub generations_per_second: f32,
+}
+
+impl Default for BoardSettings {
+    fn default() -> Self {
+        Self {
+            grid_size: super::GRID_SIZE,
+            cell_size: SPRITE_SIZE,
+            rule: Rule::default(),
+            topology: Topology::Bounded,
+            generations_per_second: GENERATIONS_PER_SECOND,
+        }
+    }
+}
+
+impl BoardSettings {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.grid_size && y >= 0 && y < self.grid_size
+    }
+
+    pub fn world_to_grid(&self, world: Vec2) -> (i32, i32) {
+        (
+            (world.x / self.cell_size).round() as i32,
+            (world.y / self.cell_size).round() as i32,
+        )
+    }
+
+    pub fn grid_to_world(&self, x: i32, y: i32) -> Vec2 {
+        Vec2::new(x as f32, y as f32) * self.cell_size
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (x + y * self.grid_size) as usize
+    }
+}
+
+// life-like rule in B/S notation, e.g. "B3/S23" for Conway's game of life
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rule {
+    birth: [bool; 9],
+    survival: [bool; 9],
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::parse("B3/S23").unwrap()
+    }
+}
+
+impl Rule {
+    pub fn parse(notation: &str) -> Result<Self, String> {
+        let mut rule = Rule {
+            birth: [false; 9],
+            survival: [false; 9],
+        };
+
+        let notation = notation.trim().to_uppercase();
+        let (birth, survival) = notation
+            .split_once('/')
+            .ok_or_else(|| format!("\"{}\" is not in B../S.. notation", notation))?;
+        for (part, prefix, counts) in [(birth, 'B', &mut rule.birth), (survival, 'S', &mut rule.survival)] {
+            let digits = part
+                .strip_prefix(prefix)
+                .ok_or_else(|| format!("expected \"{}\" to start with {}", part, prefix))?;
+            for digit in digits.chars() {
+                match digit.to_digit(10) {
+                    Some(count) if count <= 8 => counts[count as usize] = true,
+                    _ => return Err(format!("{} is not a neighbour count", digit)),
+                }
+            }
+        }
+        Ok(rule)
+    }
+
+    pub fn next_state(&self, state: CellState, neighbour_count: u32) -> CellState {
+        let neighbour_count = neighbour_count as usize;
+        match state {
+            CellState::Alive if self.survival[neighbour_count] => CellState::Alive,
+            CellState::Alive => CellState::Dead,
+            _ if self.birth[neighbour_count] => CellState::Alive,
+            state => state,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = |counts: &[bool; 9]| {
+            (0..9).filter(|&i| counts[i]).map(|i| i.to_string()).collect::<String>()
+        };
+        write!(f, "B{}/S{}", counts(&self.birth), counts(&self.survival))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Topology {
+    // cells past the edge are always dead
+    Bounded,
+    // the edges wrap around to the opposite side
+    Toroidal,
+}
+// endregion:   Settings
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum CellInteraction {
@@ -75,43 +220,89 @@ pub enum CellInteraction {
     Simulation,
 }
 
-fn setup(
+fn rebuild_board(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    settings: Res<BoardSettings>,
+    sprite_images: Res<SpriteImages>,
+    cells: Query<(Entity, &Cell)>,
+    mut built_dimensions: Local<Option<(i32, f32)>>,
 ) {
-    for x in 0..super::GRID_SIZE {
-        for y in 0..super::GRID_SIZE {
+    let dimensions = (settings.grid_size, settings.cell_size);
+    if *built_dimensions == Some(dimensions) {
+        return;
+    }
+    *built_dimensions = Some(dimensions);
+
+    // keep whatever part of the current pattern still fits
+    let mut states = vec![CellState::Empty; (settings.grid_size * settings.grid_size) as usize];
+    for (entity, cell) in cells.iter() {
+        if settings.contains(cell.x, cell.y) {
+            states[settings.index(cell.x, cell.y)] = cell.state;
+        }
+        commands.entity(entity).despawn();
+    }
+
+    for x in 0..settings.grid_size {
+        for y in 0..settings.grid_size {
+            let state = states[settings.index(x, y)];
             commands
                 .spawn_bundle(SpriteBundle {
                     transform: Transform {
-                        translation: Vec3::new((x as f32) * SPRITE_SIZE, (y as f32) * SPRITE_SIZE, 0.0),
+                        translation: settings.grid_to_world(x, y).extend(0.0),
                         scale: Vec3::new(1.0, 1.0, 1.0),
                         ..Default::default()
                     },
                     sprite: Sprite {
+                        custom_size: Some(Vec2::splat(settings.cell_size)),
                         ..Default::default()
                     },
-                    texture: asset_server.load("sprites/empty_cell.png"),
+                    // filled in by the theme until it has loaded
+                    texture: sprite_images.for_state(state),
                     ..Default::default()
                 })
                 .insert(Cell {
-                    state: CellState::Empty
+                    state,
+                    x,
+                    y,
                 });
         }
     }
+}
 
-    commands
-        .insert_resource(SpriteImages {
-            alive_cell: asset_server.load("sprites/alive_cell.png"),
-            dead_cell: asset_server.load("sprites/dead_cell.png"),
-            empty_cell: asset_server.load("sprites/empty_cell.png")
-        });
+fn apply_simulation_speed(
+    settings: Res<BoardSettings>,
+    mut timer: ResMut<SimulationTimer>,
+) {
+    if settings.is_changed() {
+        timer.0.set_duration(std::time::Duration::from_secs_f32(1.0 / settings.generations_per_second));
+    }
+}
+
+fn apply_cell_theme(
+    current_theme: Option<Res<CurrentTheme>>,
+    mut sprite_images: ResMut<SpriteImages>,
+    mut cells: Query<(&Cell, &mut Handle<Image>)>,
+) {
+    let theme = match current_theme {
+        Some(ref current_theme) if current_theme.is_changed() => &current_theme.0,
+        _ => return,
+    };
+
+    *sprite_images = SpriteImages {
+        alive_cell: theme.alive_cell.clone(),
+        dead_cell: theme.dead_cell.clone(),
+        empty_cell: theme.empty_cell.clone(),
+    };
+    for (cell, mut sprite) in cells.iter_mut() {
+        *sprite = sprite_images.for_state(cell.state);
+    }
 }
 
 fn set_cursor_world_position(
     windows: Res<Windows>,
     main_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
-    mouse_btn: Res<Input<MouseButton>>,
+    actions: Res<ActionState>,
+    ui_interactions: Query<&Interaction>,
     mut mouse_world_pos_draw: ResMut<MouseWorldPositionDraw>,
     mut mouse_world_pos_erase: ResMut<MouseWorldPositionErase>,
     is_running: Res<IsSimulationRunning>,
@@ -121,20 +312,25 @@ fn set_cursor_world_position(
         return;
     }
 
+    // don't paint through buttons and other ui widgets
+    if ui_interactions.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+
     if let Some(pos) = window.cursor_position() {
         let (transform, proj) = main_camera.single();
         let pos_world = get_mouse_world(pos, transform, window, proj);
 
-        if mouse_btn.pressed(MouseButton::Left) {
+        if actions.pressed(Action::Paint) {
             *mouse_world_pos_draw = MouseWorldPositionDraw(Some((pos_world.x, pos_world.y)));
         }
-        if mouse_btn.pressed(MouseButton::Right) {
+        if actions.pressed(Action::Erase) {
             *mouse_world_pos_erase = MouseWorldPositionErase(Some((pos_world.x, pos_world.y)));
         }
     }
 }
 
-fn get_mouse_world(
+pub fn get_mouse_world(
     pos: Vec2,
     main_transform: &Transform,
     window: &Window,
@@ -158,21 +354,23 @@ fn  cell_interaction(
     mut mouse_world_pos_draw: ResMut<MouseWorldPositionDraw>,
     mut mouse_world_pos_erase: ResMut<MouseWorldPositionErase>,
     sprite_images: Res<SpriteImages>,
+    settings: Res<BoardSettings>,
     is_running: Res<IsSimulationRunning>,
 ) {
+    let half_cell = settings.cell_size / 2.0;
     let mouse_draw = mouse_world_pos_draw.0.take();
     let mouse_erase = mouse_world_pos_erase.0.take();
 
     if !is_running.0 && (mouse_draw.is_some() || mouse_erase.is_some()) {
         for (mut cell, mut sprite, transform) in cells.iter_mut() {
             if let Some(mouse_world_pos) = mouse_draw {
-                if is_in_cell_bounds(mouse_world_pos, (transform.translation.x, transform.translation.y), (SPRITE_SIZE/2.0, SPRITE_SIZE/2.0)) {
+                if is_in_cell_bounds(mouse_world_pos, (transform.translation.x, transform.translation.y), (half_cell, half_cell)) {
                     cell.state = CellState::Alive;
                     *sprite = sprite_images.alive_cell.clone();
                 }
             }
             if let Some(mouse_world_pos) = mouse_erase {
-                if is_in_cell_bounds(mouse_world_pos, (transform.translation.x, transform.translation.y), (SPRITE_SIZE/2.0, SPRITE_SIZE/2.0)) {
+                if is_in_cell_bounds(mouse_world_pos, (transform.translation.x, transform.translation.y), (half_cell, half_cell)) {
                     cell.state = CellState::Empty;
                     *sprite = sprite_images.empty_cell.clone();
                 }
@@ -193,51 +391,66 @@ fn simulation_step(
     mut cells: Query<(&mut Cell, &mut Handle<Image>)>,
     is_running: Res<IsSimulationRunning>,
     sprite_images: Res<SpriteImages>,
+    settings: Res<BoardSettings>,
+    time: Res<Time>,
+    mut timer: ResMut<SimulationTimer>,
 ) {
     if !is_running.0 {
         return;
     }
-    let mut life_grid: Vec<bool> = Vec::new();
-    for (cell, _) in cells.iter_mut() {
-        life_grid.push(
-            match cell.state {
-                CellState::Alive => true,
-                CellState::Dead | CellState::Empty => false,
-            }
-        );
-    }
-
-    for (index, (mut cell, mut sprite)) in cells.iter_mut().enumerate() {
-        let mut neighbour_count = 0;
-        let x = index as i32 % super::GRID_SIZE;
-        let y = index as i32 / super::GRID_SIZE;
-        
-        for xi in (x-1)..(x+2) {
-            for yi in (y-1)..(y+2) {
-                if (xi != x || yi != y) && xi >= 0 && xi < super::GRID_SIZE && yi >= 0 && yi < super::GRID_SIZE {
-                    let oneD_index = xi + yi * super::GRID_SIZE;
-                    if life_grid[oneD_index as usize] {
-                        neighbour_count += 1;
-                    }
-                }
-            }
+
+    timer.0.tick(time.delta());
+    for _ in 0..timer.0.times_finished() {
+        advance_generation(&mut cells, &sprite_images, &settings);
+    }
+}
+
+fn advance_generation(
+    cells: &mut Query<(&mut Cell, &mut Handle<Image>)>,
+    sprite_images: &SpriteImages,
+    settings: &BoardSettings,
+) {
+    let life_grid = life_grid(cells.iter().map(|(cell, _)| cell), settings);
+
+    for (mut cell, mut sprite) in cells.iter_mut() {
+        let neighbour_count = count_neighbours(&life_grid, settings, cell.x, cell.y);
+
+        let next = settings.rule.next_state(cell.state, neighbour_count);
+        if next != cell.state {
+            cell.state = next;
+            *sprite = sprite_images.for_state(next);
         }
+    }
+}
 
-        if neighbour_count < 2 || neighbour_count > 3 {
-            match cell.state {
-                CellState::Alive => {
-                    cell.state = CellState::Dead;
-                    *sprite = sprite_images.dead_cell.clone();
-                }
-                CellState::Dead | CellState::Empty => {}
+pub fn count_neighbours(life_grid: &[bool], settings: &BoardSettings, x: i32, y: i32) -> u32 {
+    let mut neighbour_count = 0;
+    for xi in (x-1)..(x+2) {
+        for yi in (y-1)..(y+2) {
+            if xi == x && yi == y {
+                continue;
+            }
+
+            let (xi, yi) = match settings.topology {
+                Topology::Bounded => (xi, yi),
+                Topology::Toroidal => (xi.rem_euclid(settings.grid_size), yi.rem_euclid(settings.grid_size)),
+            };
+            if settings.contains(xi, yi) && life_grid[settings.index(xi, yi)] {
+                neighbour_count += 1;
             }
         }
+    }
+    neighbour_count
+}
 
-        if neighbour_count == 3 {
-            cell.state = CellState::Alive;
-            *sprite = sprite_images.alive_cell.clone();
+pub fn life_grid<'a>(cells: impl Iterator<Item = &'a Cell>, settings: &BoardSettings) -> Vec<bool> {
+    let mut life_grid = vec![false; (settings.grid_size * settings.grid_size) as usize];
+    for cell in cells {
+        if cell.state == CellState::Alive && settings.contains(cell.x, cell.y) {
+            life_grid[settings.index(cell.x, cell.y)] = true;
         }
     }
+    life_grid
 }
 
 fn exit_game(
@@ -249,20 +462,103 @@ fn exit_game(
     }
 }
 
-fn set_simulation(
-    mut event_reader: EventReader<SimulationStartEvent>,
-    mut start_sim: ResMut<IsSimulationRunning>,
+fn toggle_simulation(
+    mut event_reader: EventReader<SimulationToggleEvent>,
+    mut is_running: ResMut<IsSimulationRunning>,
 ) {
-    if event_reader.iter().next().is_some() {
-        start_sim.0 = true;
+    // an even number of toggles in one frame cancels out
+    if event_reader.iter().count() % 2 == 1 {
+        is_running.0 = !is_running.0;
     }
 }
 
-fn unset_simulation(
-    mut event_reader: EventReader<SimulationStopEvent>,
-    mut start_sim: ResMut<IsSimulationRunning>,
+fn step_simulation(
+    mut event_reader: EventReader<SimulationStepEvent>,
+    mut cells: Query<(&mut Cell, &mut Handle<Image>)>,
+    is_running: Res<IsSimulationRunning>,
+    sprite_images: Res<SpriteImages>,
+    settings: Res<BoardSettings>,
 ) {
-    if event_reader.iter().next().is_some() {
-        start_sim.0 = false;
+    for _ in event_reader.iter() {
+        if !is_running.0 {
+            advance_generation(&mut cells, &sprite_images, &settings);
+        }
+    }
+}
+
+fn clear_board(
+    mut event_reader: EventReader<BoardClearEvent>,
+    mut cells: Query<(&mut Cell, &mut Handle<Image>)>,
+    sprite_images: Res<SpriteImages>,
+) {
+    if event_reader.iter().next().is_none() {
+        return;
+    }
+
+    for (mut cell, mut sprite) in cells.iter_mut() {
+        if cell.state != CellState::Empty {
+            cell.state = CellState::Empty;
+            *sprite = sprite_images.empty_cell.clone();
+        }
+    }
+}
+
+fn randomize_board(
+    mut event_reader: EventReader<BoardRandomizeEvent>,
+    mut cells: Query<(&mut Cell, &mut Handle<Image>)>,
+    sprite_images: Res<SpriteImages>,
+) {
+    if event_reader.iter().next().is_none() {
+        return;
+    }
+
+    let mut rng = thread_rng();
+    for (mut cell, mut sprite) in cells.iter_mut() {
+        cell.state = if rng.gen_bool(RANDOM_ALIVE_CHANCE) {
+            CellState::Alive
+        } else {
+            CellState::Empty
+        };
+        *sprite = sprite_images.for_state(cell.state);
+    }
+}
+
+fn save_board(
+    mut event_reader: EventReader<BoardSaveEvent>,
+    cells: Query<&Cell>,
+    settings: Res<BoardSettings>,
+) {
+    if event_reader.iter().next().is_none() {
+        return;
+    }
+
+    // plaintext ".cells" format: one line per row from the top, 'O' alive and '.' otherwise
+    let grid_size = settings.grid_size;
+    let mut rows = vec![vec!['.'; grid_size as usize]; grid_size as usize];
+    for cell in cells.iter() {
+        if cell.state == CellState::Alive && settings.contains(cell.x, cell.y) {
+            rows[(grid_size - 1 - cell.y) as usize][cell.x as usize] = 'O';
+        }
+    }
+    let mut contents = String::from("!Name: bevytuts board\n");
+    for row in rows {
+        contents.extend(row);
+        contents.push('\n');
+    }
+
+    let path = match data_path(SAVE_FILE) {
+        Some(path) => path,
+        None => {
+            warn!("No data directory available, board not saved");
+            return;
+        }
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, contents));
+    match result {
+        Ok(()) => info!("Saved board to {}", path.display()),
+        Err(e) => warn!("Could not save board to {}: {}", path.display(), e),
     }
 }
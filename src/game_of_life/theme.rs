@@ -0,0 +1,191 @@
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::input_map::{Action, ActionState};
+
+const BUILT_IN_THEMES: [&str; 3] = [
+    "themes/default.theme.ron",
+    "themes/high_contrast.theme.ron",
+    "themes/colorblind.theme.ron",
+];
+
+// region:      Theme asset
+// what is written in the .theme.ron files
+#[derive(Deserialize)]
+struct ThemeDescription {
+    name: String,
+    clear_color: Color,
+    text_color: Color,
+    panel_color: Color,
+    panel_fill_color: Color,
+    overlay_color: Color,
+    normal_button: Color,
+    hovered_button: Color,
+    pressed_button: Color,
+    running_button: Color,
+    font: String,
+    button_image: String,
+    alive_cell: String,
+    dead_cell: String,
+    empty_cell: String,
+}
+
+#[derive(Clone, TypeUuid)]
+#[uuid = "6b0bd1c7-3f27-4f5e-9a43-0f3b8a8f51d2"]
+pub struct Theme {
+    pub name: String,
+    pub clear_color: Color,
+    pub text_color: Color,
+    pub panel_color: Color,
+    pub panel_fill_color: Color,
+    pub overlay_color: Color,
+    pub normal_button: Color,
+    pub hovered_button: Color,
+    pub pressed_button: Color,
+    pub running_button: Color,
+    pub font: Handle<Font>,
+    pub button_image: Handle<Image>,
+    pub alive_cell: Handle<Image>,
+    pub dead_cell: Handle<Image>,
+    pub empty_cell: Handle<Image>,
+}
+
+#[derive(Default)]
+struct ThemeLoader;
+
+impl AssetLoader for ThemeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let description: ThemeDescription = ron::de::from_bytes(bytes)?;
+
+            // the textures and font are dependencies, so they get (re)loaded along with the theme
+            let dependencies: Vec<AssetPath<'static>> = [
+                &description.font,
+                &description.button_image,
+                &description.alive_cell,
+                &description.dead_cell,
+                &description.empty_cell,
+            ]
+            .iter()
+            .map(|path| AssetPath::from(path.as_str()).to_owned())
+            .collect();
+
+            let theme = Theme {
+                name: description.name,
+                clear_color: description.clear_color,
+                text_color: description.text_color,
+                panel_color: description.panel_color,
+                panel_fill_color: description.panel_fill_color,
+                overlay_color: description.overlay_color,
+                normal_button: description.normal_button,
+                hovered_button: description.hovered_button,
+                pressed_button: description.pressed_button,
+                running_button: description.running_button,
+                font: load_context.get_handle(description.font.as_str()),
+                button_image: load_context.get_handle(description.button_image.as_str()),
+                alive_cell: load_context.get_handle(description.alive_cell.as_str()),
+                dead_cell: load_context.get_handle(description.dead_cell.as_str()),
+                empty_cell: load_context.get_handle(description.empty_cell.as_str()),
+            };
+
+            load_context.set_default_asset(LoadedAsset::new(theme).with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
+// endregion:   Theme asset
+
+// region:      Resources
+// Resource
+struct ThemeLibrary {
+    themes: Vec<Handle<Theme>>,
+    current: usize,
+}
+
+impl ThemeLibrary {
+    fn current(&self) -> &Handle<Theme> {
+        &self.themes[self.current]
+    }
+}
+
+// Resource, only inserted once the selected theme has loaded
+pub struct CurrentTheme(pub Theme);
+// endregion:   Resources
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<Theme>()
+            .init_asset_loader::<ThemeLoader>()
+            .add_startup_system(setup)
+            .add_system(cycle_theme)
+            .add_system(track_current_theme.after(cycle_theme))
+            .add_system(apply_clear_color.after(track_current_theme));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(ThemeLibrary {
+        themes: BUILT_IN_THEMES.iter().map(|path| asset_server.load(*path)).collect(),
+        current: 0,
+    });
+}
+
+fn cycle_theme(
+    actions: Res<ActionState>,
+    mut library: ResMut<ThemeLibrary>,
+) {
+    if actions.just_pressed(Action::CycleTheme) {
+        library.current = (library.current + 1) % library.themes.len();
+    }
+}
+
+fn track_current_theme(
+    mut commands: Commands,
+    mut theme_events: EventReader<AssetEvent<Theme>>,
+    library: Res<ThemeLibrary>,
+    themes: Res<Assets<Theme>>,
+) {
+    // (re)load when switching themes, or when the current theme file is created or hot reloaded
+    let current = library.current();
+    let current_touched = theme_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle == current,
+        AssetEvent::Removed { .. } => false,
+    });
+
+    if library.is_changed() || current_touched {
+        if let Some(theme) = themes.get(current) {
+            info!("Using theme \"{}\"", theme.name);
+            commands.insert_resource(CurrentTheme(theme.clone()));
+        }
+    }
+}
+
+fn apply_clear_color(
+    current_theme: Option<Res<CurrentTheme>>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if let Some(current_theme) = current_theme {
+        if current_theme.is_changed() {
+            clear_color.0 = current_theme.0.clear_color;
+        }
+    }
+}
@@ -1,21 +1,49 @@
-use bevy::{prelude::*, ecs::system::Command};
+use bevy::prelude::*;
 
-const NORMAL_BUTTON: Color = Color::rgb(0.0, 0.8, 0.8);
-const HOVERED_BUTTON: Color = Color::rgb(0.4, 0.4, 0.0);
-const PRESSED_BUTTON: Color = Color::rgb(0.6, 0.0, 0.6);
+use super::simulation::IsSimulationRunning;
+use super::theme::CurrentTheme;
+use crate::input_map::{Action, ActionState, InputMap};
+use crate::widgets::{spawn_button, spawn_toggle, Toggle, WidgetAction, WidgetEvent, WidgetStyle};
+
+const PLAY_PAUSE: WidgetAction = WidgetAction("gol.play_pause");
+const QUIT: WidgetAction = WidgetAction("gol.quit");
+
+const SHORTCUTS: [(Action, &str); 17] = [
+    (Action::TogglePlay, "play / pause"),
+    (Action::Step, "step one generation"),
+    (Action::ClearBoard, "clear board"),
+    (Action::RandomizeBoard, "randomize board"),
+    (Action::SaveBoard, "save board"),
+    (Action::ToggleGrid, "toggle grid"),
+    (Action::CycleTheme, "next theme"),
+    (Action::ToggleHelp, "toggle this help"),
+    (Action::Paint, "paint cell"),
+    (Action::Erase, "erase cell"),
+    (Action::PanUp, "pan up"),
+    (Action::PanDown, "pan down"),
+    (Action::PanLeft, "pan left"),
+    (Action::PanRight, "pan right"),
+    (Action::ZoomIn, "zoom in"),
+    (Action::ZoomOut, "zoom out"),
+    (Action::CameraStop, "stop camera"),
+];
 
 pub struct GameExitEvent;
-pub struct SimulationStartEvent;
-pub struct SimulationStopEvent;
+pub struct SimulationToggleEvent;
+pub struct SimulationStepEvent;
+pub struct BoardClearEvent;
+pub struct BoardRandomizeEvent;
+pub struct BoardSaveEvent;
 
 #[derive(Component)]
-struct ClassicButton(ButtonType);
+struct HelpOverlay;
 
-#[derive(PartialEq, Clone, Copy)]
-enum ButtonType {
-    Start,
-    Stop,
-    Exit,
+// background nodes coloured by the theme
+#[derive(Component, Clone, Copy)]
+pub enum ThemedPanel {
+    Border,
+    Fill,
+    Overlay,
 }
 
 
@@ -25,16 +53,23 @@ impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_event::<GameExitEvent>()
-            .add_event::<SimulationStartEvent>()
-            .add_event::<SimulationStopEvent>()
+            .add_event::<SimulationToggleEvent>()
+            .add_event::<SimulationStepEvent>()
+            .add_event::<BoardClearEvent>()
+            .add_event::<BoardRandomizeEvent>()
+            .add_event::<BoardSaveEvent>()
             .add_startup_system(setup)
-            .add_system(button_system);
+            .add_system(widget_actions)
+            .add_system(play_pause_toggle)
+            .add_system(keyboard_shortcuts)
+            .add_system(toggle_help)
+            .add_system(apply_ui_theme);
     }
 }
 
 fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    input_map: Res<InputMap>,
 ) {
     commands.spawn_bundle(UiCameraBundle::default());
 
@@ -56,9 +91,9 @@ fn setup(
                         border: Rect::all(Val::Px(5.0)),
                         ..Default::default()
                     },
-                    color: Color::rgb(0.1, 0.1, 0.1).into(),
                     ..Default::default()
                 })
+                .insert(ThemedPanel::Border)
                 .with_children(|parent| {
                     parent
                         .spawn_bundle(NodeBundle {
@@ -67,101 +102,186 @@ fn setup(
                                 align_items: AlignItems::FlexEnd,
                                 ..Default::default()
                             },
-                            color: Color::rgb(0.2, 0.2, 0.2).into(),
                             ..Default::default()
                         })
+                        .insert(ThemedPanel::Fill)
                         .with_children(|parent| {
-                            parent
-                                .spawn_bundle(build_classic_button(&asset_server))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn_bundle(build_classic_text("PLAY", &asset_server));
-                                })
-                                .insert(ClassicButton(ButtonType::Start));
-
-                            parent
-                                .spawn_bundle(build_classic_button(&asset_server))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn_bundle(build_classic_text("STOP", &asset_server));
-                                })
-                                .insert(ClassicButton(ButtonType::Stop));
-
-                            parent
-                                .spawn_bundle(build_classic_button(&asset_server))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn_bundle(build_classic_text("QUIT", &asset_server));
-                                })
-                                .insert(ClassicButton(ButtonType::Exit));
+                            let play_pause = Toggle {
+                                on: false,
+                                off_label: "PLAY".to_string(),
+                                on_label: "PAUSE".to_string(),
+                            };
+                            spawn_toggle(parent, PLAY_PAUSE, play_pause, classic_button_style(), 38.0);
+                            spawn_button(parent, QUIT, "QUIT", classic_button_style(), 38.0);
                             });
                 });
         });
-}
 
-fn build_classic_button(
-    asset_server: &Res<AssetServer>,
-) -> ButtonBundle {
-    ButtonBundle {
-        style: Style {
-            size: Size::new(Val::Px(150.0), Val::Px(50.0)),
-            margin: Rect::all(Val::Auto),
-            justify_content: JustifyContent::Center,
-            align_items: AlignItems::Center,
+    // shortcut help, hidden until toggled
+    let help_text = SHORTCUTS
+        .iter()
+        .map(|&(action, description)| {
+            let keys = input_map
+                .bindings(action)
+                .iter()
+                .map(|binding| binding.to_string())
+                .collect::<Vec<_>>()
+                .join(" / ");
+            format!("{:<24}{}", keys, description)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(40.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(10.0)),
+                display: Display::None,
+                ..Default::default()
+            },
             ..Default::default()
-        },
-        color: NORMAL_BUTTON.into(),
-        image: UiImage(asset_server.load("sprites/button.png")),
-        ..Default::default()
-    }
+        })
+        .insert(HelpOverlay)
+        .insert(ThemedPanel::Overlay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    help_text,
+                    TextStyle {
+                        font_size: 18.0,
+                        ..Default::default()
+                    },
+                    Default::default()
+                ),
+                ..Default::default()
+            });
+        });
 }
 
-fn build_classic_text(
-    value: &str,
-    asset_server: &Res<AssetServer>,
-) -> TextBundle {
-    TextBundle {
-        text: Text::with_section(
-            value,
-            TextStyle {
-                font: asset_server.load("fonts/Symtext.ttf"),
-                font_size: 38.0,
-                color: Color::WHITE,
-            },
-            Default::default()
-        ),
+fn classic_button_style() -> Style {
+    Style {
+        size: Size::new(Val::Px(150.0), Val::Px(50.0)),
+        margin: Rect::all(Val::Auto),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
         ..Default::default()
     }
 }
 
-fn button_system(
-    mut interaction_query: Query<(&Interaction, &mut UiColor, &ClassicButton), (Changed<Interaction>, With<Button>)>,
-    mut start_writer: EventWriter<SimulationStartEvent>,
-    mut stop_writer: EventWriter<SimulationStopEvent>,
+fn widget_actions(
+    mut widget_events: EventReader<WidgetEvent>,
+    mut toggle_writer: EventWriter<SimulationToggleEvent>,
     mut exit_writer: EventWriter<GameExitEvent>,
 ) {
-    for (interaction, mut color, classic_button) in interaction_query.iter_mut() {
-        match *interaction {
-            Interaction::Clicked => {
-                *color = PRESSED_BUTTON.into();
-                match classic_button.0 {
-                    ButtonType::Start => {
-                        start_writer.send(SimulationStartEvent)
-                    }
-                    ButtonType::Stop => {
-                        stop_writer.send(SimulationStopEvent)
-                    }
-                    ButtonType::Exit => {
-                        exit_writer.send(GameExitEvent)
-                    }
-                }
-            },
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
-            },
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
-            },
+    for event in widget_events.iter() {
+        match event.action {
+            PLAY_PAUSE => toggle_writer.send(SimulationToggleEvent),
+            QUIT => exit_writer.send(GameExitEvent),
+            _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+// the simulation can also be toggled from the keyboard, keep the toggle in step with it
+fn play_pause_toggle(
+    is_running: Res<IsSimulationRunning>,
+    mut toggles: Query<(&mut Toggle, &WidgetAction)>,
+) {
+    if !is_running.is_changed() {
+        return;
+    }
+
+    for (mut toggle, action) in toggles.iter_mut() {
+        if *action == PLAY_PAUSE && toggle.on != is_running.0 {
+            toggle.on = is_running.0;
+        }
+    }
+}
+
+fn keyboard_shortcuts(
+    actions: Res<ActionState>,
+    mut toggle_writer: EventWriter<SimulationToggleEvent>,
+    mut step_writer: EventWriter<SimulationStepEvent>,
+    mut clear_writer: EventWriter<BoardClearEvent>,
+    mut randomize_writer: EventWriter<BoardRandomizeEvent>,
+    mut save_writer: EventWriter<BoardSaveEvent>,
+) {
+    if actions.just_pressed(Action::TogglePlay) {
+        toggle_writer.send(SimulationToggleEvent);
+    }
+    if actions.just_pressed(Action::Step) {
+        step_writer.send(SimulationStepEvent);
+    }
+    if actions.just_pressed(Action::ClearBoard) {
+        clear_writer.send(BoardClearEvent);
+    }
+    if actions.just_pressed(Action::RandomizeBoard) {
+        randomize_writer.send(BoardRandomizeEvent);
+    }
+    if actions.just_pressed(Action::SaveBoard) {
+        save_writer.send(BoardSaveEvent);
+    }
+}
+
+fn toggle_help(
+    actions: Res<ActionState>,
+    mut help: Query<&mut Style, With<HelpOverlay>>,
+) {
+    if !actions.just_pressed(Action::ToggleHelp) {
+        return;
+    }
+
+    for mut style in help.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            Display::Flex => Display::None,
+        };
+    }
+}
+
+fn apply_ui_theme(
+    current_theme: Option<Res<CurrentTheme>>,
+    mut widget_style: ResMut<WidgetStyle>,
+    mut panels: Query<(&mut UiColor, &ThemedPanel)>,
+    mut texts: Query<&mut Text>,
+) {
+    let theme = match current_theme {
+        Some(ref current_theme) if current_theme.is_changed() => &current_theme.0,
+        _ => return,
+    };
+
+    *widget_style = WidgetStyle {
+        font: theme.font.clone(),
+        text_color: theme.text_color,
+        normal: theme.normal_button,
+        hovered: theme.hovered_button,
+        pressed: theme.pressed_button,
+        active: theme.running_button,
+        track: theme.panel_fill_color,
+        button_image: theme.button_image.clone(),
+    };
+
+    for (mut color, panel) in panels.iter_mut() {
+        *color = match panel {
+            ThemedPanel::Border => theme.panel_color,
+            ThemedPanel::Fill => theme.panel_fill_color,
+            ThemedPanel::Overlay => theme.overlay_color,
+        }.into();
+    }
+
+    // every text in the game, keeping the transparency each one was spawned with
+    for mut text in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            let alpha = section.style.color.a();
+            section.style.font = theme.font.clone();
+            section.style.color = theme.text_color;
+            section.style.color.set_a(alpha);
+        }
+    }
+}
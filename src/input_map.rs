@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use bevy::{
+    prelude::*,
+    input::InputSystem,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::load_config;
+
+const INPUT_MAP_FILE: &str = "input_map.ron";
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+// region:      Actions and bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    // game of life camera and painting
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    CameraStop,
+    Paint,
+    Erase,
+    ToggleGrid,
+    TogglePlay,
+    Step,
+    ClearBoard,
+    RandomizeBoard,
+    SaveBoard,
+    ToggleHelp,
+    CycleTheme,
+    // invaders ship
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Pause,
+    ToggleHitboxes,
+    ToggleAutofire,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    GamepadAxis(GamepadAxisType, AxisDirection),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(button) => write!(f, "{:?} mouse", button),
+            Binding::GamepadButton(button) => write!(f, "pad {:?}", button),
+            Binding::GamepadAxis(axis, AxisDirection::Positive) => write!(f, "pad {:?}+", axis),
+            Binding::GamepadAxis(axis, AxisDirection::Negative) => write!(f, "pad {:?}-", axis),
+        }
+    }
+}
+// endregion:   Actions and bindings
+
+// region:      Resources
+// Resource
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Action::*;
+        use Binding::*;
+
+        let bindings = HashMap::from([
+            (PanUp, vec![Key(KeyCode::W), GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Positive)]),
+            (PanDown, vec![Key(KeyCode::S), GamepadAxis(GamepadAxisType::LeftStickY, AxisDirection::Negative)]),
+            (PanLeft, vec![Key(KeyCode::A), GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Negative)]),
+            (PanRight, vec![Key(KeyCode::D), GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Positive)]),
+            (ZoomIn, vec![Key(KeyCode::E), GamepadButton(GamepadButtonType::RightTrigger)]),
+            (ZoomOut, vec![Key(KeyCode::Q), GamepadButton(GamepadButtonType::LeftTrigger)]),
+            (CameraStop, vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::East)]),
+            (Paint, vec![Mouse(MouseButton::Left)]),
+            (Erase, vec![Mouse(MouseButton::Right)]),
+            (ToggleGrid, vec![Key(KeyCode::G)]),
+            (TogglePlay, vec![Key(KeyCode::P), GamepadButton(GamepadButtonType::Start)]),
+            (Step, vec![Key(KeyCode::N), GamepadButton(GamepadButtonType::North)]),
+            (ClearBoard, vec![Key(KeyCode::C)]),
+            (RandomizeBoard, vec![Key(KeyCode::R)]),
+            (SaveBoard, vec![Key(KeyCode::F5)]),
+            (CycleTheme, vec![Key(KeyCode::T)]),
+            (ToggleHelp, vec![Key(KeyCode::H), Key(KeyCode::F1), GamepadButton(GamepadButtonType::Select)]),
+            (MoveLeft, vec![Key(KeyCode::Left), GamepadButton(GamepadButtonType::DPadLeft), GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Negative)]),
+            (MoveRight, vec![Key(KeyCode::Right), GamepadButton(GamepadButtonType::DPadRight), GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Positive)]),
+            (Fire, vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::South)]),
+            (Pause, vec![Key(KeyCode::Escape), GamepadButton(GamepadButtonType::Start)]),
+            (ToggleHitboxes, vec![Key(KeyCode::F3)]),
+            (ToggleAutofire, vec![Key(KeyCode::F), GamepadButton(GamepadButtonType::North)]),
+        ]);
+
+        Self { bindings }
+    }
+}
+
+impl InputMap {
+    // default table with the user's overrides (if any) replacing whole actions
+    fn load() -> Self {
+        let mut input_map = Self::default();
+        input_map.bindings.extend(load_config::<HashMap<Action, Vec<Binding>>>(INPUT_MAP_FILE));
+        input_map
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+// Resource
+#[derive(Default)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    pressed: HashSet<Action>,
+    previously_pressed: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action) && !self.previously_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.pressed.contains(&action) && self.previously_pressed.contains(&action)
+    }
+
+    // analog strength in 0..=1 (digital bindings are either 0 or 1)
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+}
+
+// Resource, set while a text field has focus so typing doesn't trigger key bindings
+#[derive(Default)]
+pub struct KeyboardCapture(pub bool);
+// endregion:   Resources
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(InputMap::load())
+            .init_resource::<ActionState>()
+            .init_resource::<KeyboardCapture>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_action_state.after(InputSystem)
+            );
+    }
+}
+
+pub fn update_action_state(
+    input_map: Res<InputMap>,
+    mut action_state: ResMut<ActionState>,
+    keyboard_capture: Res<KeyboardCapture>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+) {
+    let action_state = &mut *action_state;
+    action_state.previously_pressed = std::mem::take(&mut action_state.pressed);
+    action_state.values.clear();
+
+    for (&action, bindings) in input_map.bindings.iter() {
+        let mut value: f32 = 0.0;
+        for binding in bindings {
+            let binding_value = match *binding {
+                Binding::Key(key) => digital(!keyboard_capture.0 && keyboard_input.pressed(key)),
+                Binding::Mouse(button) => digital(mouse_input.pressed(button)),
+                Binding::GamepadButton(button_type) => digital(
+                    gamepads
+                        .iter()
+                        .any(|&gamepad| gamepad_buttons.pressed(GamepadButton(gamepad, button_type)))
+                ),
+                Binding::GamepadAxis(axis_type, direction) => gamepads
+                    .iter()
+                    .filter_map(|&gamepad| gamepad_axes.get(GamepadAxis(gamepad, axis_type)))
+                    .map(|axis_value| match direction {
+                        AxisDirection::Positive => axis_value.max(0.0),
+                        AxisDirection::Negative => (-axis_value).max(0.0),
+                    })
+                    .fold(0.0, f32::max),
+            };
+            value = value.max(binding_value);
+        }
+
+        if value > 0.0 {
+            action_state.values.insert(action, value);
+        }
+        if value >= AXIS_PRESS_THRESHOLD {
+            action_state.pressed.insert(action);
+        }
+    }
+}
+
+fn digital(pressed: bool) -> f32 {
+    if pressed { 1.0 } else { 0.0 }
+}
@@ -0,0 +1,379 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    math::Vec3Swizzles,
+};
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    collision::LaserHitEnemy,
+    enemy::{spawn_enemy_laser, Formation},
+    hitbox::{Hitbox, Hitboxes, Shape},
+    simulation::{movement_systems, response_systems, spawn_systems, Interpolated, SimulationStage},
+    waves::{advance_wave, CurrentWave},
+    ActiveEnemies, AppState, Enemy, ExplosionToSpawn, GameRng, HitFlash, Player, RngDraw, Score,
+    Speed, SpriteInfos, Playfield, Wave, TIME_STEP,
+};
+
+const BOSS_HEALTH: u32 = 40;
+const BOSS_POINTS: u32 = 5000;
+const BOSS_SPEED: f32 = 250.0;
+const HEALTH_BAR_WIDTH: f32 = 400.0;
+const HEALTH_BAR_HEIGHT: f32 = 8.0;
+const DEATH_EXPLOSIONS: u32 = 12;
+const DEATH_EXPLOSION_INTERVAL: f32 = 0.12;
+
+// region:      Phases
+#[derive(Clone, Copy)]
+enum BossMovement {
+    // the usual formation ellipse
+    Orbit,
+    // side to side along the top
+    Sweep { frequency: f32 },
+}
+
+#[derive(Clone, Copy)]
+enum BossAttack {
+    Spread { count: u32, angle: f32 },
+    Aimed { count: u32, angle: f32 },
+}
+
+struct BossPhase {
+    // the phase lasts while the health fraction is above this
+    above: f32,
+    movement: BossMovement,
+    attacks: &'static [BossAttack],
+    fire_interval: f32,
+}
+
+const PHASES: [BossPhase; 3] = [
+    BossPhase {
+        above: 0.66,
+        movement: BossMovement::Orbit,
+        attacks: &[BossAttack::Spread { count: 5, angle: 1.2 }],
+        fire_interval: 1.2,
+    },
+    BossPhase {
+        above: 0.33,
+        movement: BossMovement::Sweep { frequency: 0.6 },
+        attacks: &[BossAttack::Aimed { count: 3, angle: 0.2 }],
+        fire_interval: 0.8,
+    },
+    BossPhase {
+        above: 0.0,
+        movement: BossMovement::Sweep { frequency: 1.2 },
+        attacks: &[
+            BossAttack::Spread { count: 7, angle: 1.6 },
+            BossAttack::Aimed { count: 1, angle: 0.0 },
+        ],
+        fire_interval: 0.6,
+    },
+];
+
+fn phase_for(health: u32) -> usize {
+    let fraction = health as f32 / BOSS_HEALTH as f32;
+    PHASES
+        .iter()
+        .position(|phase| fraction > phase.above)
+        .unwrap_or(PHASES.len() - 1)
+}
+// endregion:   Phases
+
+// region:      Components
+#[derive(Component)]
+struct Boss {
+    health: u32,
+    phase: usize,
+    phase_time: f32,
+    fire: Timer,
+}
+
+// staged destruction, explosions go off across the hull until it is gone
+#[derive(Component)]
+struct BossDying {
+    remaining: u32,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct HealthBarFill;
+
+#[derive(Component)]
+struct HealthBar;
+// endregion:   Components
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(despawn_health_bar)
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(health_bar)
+            )
+            .add_system_set_to_stage(SimulationStage, movement_systems().with_system(boss_movement))
+            .add_system_set_to_stage(SimulationStage, response_systems().with_system(boss_hit))
+            .add_system_set_to_stage(
+                SimulationStage,
+                spawn_systems()
+                    .with_system(boss_spawn.after(advance_wave))
+                    .with_system(boss_phases)
+                    .with_system(boss_fire.after(boss_phases))
+                    .with_system(boss_dying.label(RngDraw::BossDeath).after(RngDraw::EnemySpawn))
+            );
+    }
+}
+
+fn boss_spawn(
+    mut commands: Commands,
+    current_wave: Res<CurrentWave>,
+    mut wave: ResMut<Wave>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    playfield: Res<Playfield>,
+    sprite_infos: Res<SpriteInfos>,
+    hitboxes: Res<Hitboxes>,
+) {
+    let boss_wave = current_wave.0.as_ref().map_or(false, |definition| definition.boss);
+    if !boss_wave || !wave.intermission.finished() || wave.spawned > 0 {
+        return;
+    }
+
+    // fly in from above the playfield into the first phase's orbit
+    let start = Vec2::new(0.0, playfield.height / 2.0 + sprite_infos.boss.1.y);
+    let offset = Vec2::new(0.0, playfield.height / 4.0);
+    let radius = Vec2::new(playfield.width / 4.0, 60.0);
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: sprite_infos.boss.0.clone(),
+            transform: Transform::from_translation(start.extend(10.0)),
+            ..Default::default()
+        })
+        .insert(Enemy)
+        .insert(Boss {
+            health: BOSS_HEALTH,
+            phase: 0,
+            phase_time: 0.0,
+            fire: Timer::from_seconds(PHASES[0].fire_interval, true),
+        })
+        .insert(Speed(BOSS_SPEED))
+        .insert(hitboxes.boss.clone())
+        .insert(Formation::around(start, offset, radius))
+        .insert(Interpolated::at(start.extend(10.0)));
+
+    let bar_position = Vec3::new(0.0, playfield.height / 2.0 - 50.0, 20.0);
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.2, 0.2, 0.2, 0.8),
+                custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(bar_position),
+            ..Default::default()
+        })
+        .insert(HealthBar);
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.85, 0.15, 0.6),
+                custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(bar_position + Vec3::Z),
+            ..Default::default()
+        })
+        .insert(HealthBar)
+        .insert(HealthBarFill);
+
+    wave.spawned = 1;
+    active_enemies.0 += 1;
+}
+
+fn boss_hit(
+    mut commands: Commands,
+    mut hits: EventReader<LaserHitEnemy>,
+    mut boss_query: Query<&mut Boss, Without<BossDying>>,
+) {
+    for hit in hits.iter() {
+        let mut boss = match boss_query.get_mut(hit.enemy) {
+            Ok(boss) => boss,
+            Err(_) => continue,
+        };
+        // already going down from another laser this same step
+        if boss.health == 0 {
+            continue;
+        }
+
+        boss.health -= 1;
+        if boss.health == 0 {
+            commands.entity(hit.enemy).insert(BossDying {
+                remaining: DEATH_EXPLOSIONS,
+                timer: Timer::from_seconds(DEATH_EXPLOSION_INTERVAL, true),
+            });
+            continue;
+        }
+        commands.entity(hit.enemy).insert(HitFlash::default());
+    }
+}
+
+fn boss_phases(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut boss_query: Query<(Entity, &Transform, &Hitbox, &mut Boss)>,
+) {
+    for (entity, tf, hitbox, mut boss) in boss_query.iter_mut() {
+        let phase = phase_for(boss.health);
+        if phase == boss.phase {
+            continue;
+        }
+
+        boss.phase = phase;
+        boss.phase_time = 0.0;
+        boss.fire = Timer::from_seconds(PHASES[phase].fire_interval, true);
+
+        // a wing goes up in flames whenever the boss is pushed into its next phase,
+        // the hitbox file lists the wings after the core
+        let wing = hitbox.0.get(1 + phase % 2).map_or(Vec2::ZERO, |shape| shape.bounds().0) * tf.scale.xy();
+        commands
+            .spawn()
+            .insert(ExplosionToSpawn(tf.translation + wing.extend(0.0)));
+
+        match PHASES[phase].movement {
+            BossMovement::Orbit => {
+                let start = tf.translation.truncate();
+                let offset = Vec2::new(0.0, playfield.height / 4.0);
+                let radius = Vec2::new(playfield.width / 4.0, 60.0);
+                commands.entity(entity).insert(Formation::around(start, offset, radius));
+            }
+            BossMovement::Sweep { .. } => {
+                commands.entity(entity).remove::<Formation>();
+            }
+        }
+    }
+}
+
+// orbiting is left to the regular enemy movement
+fn boss_movement(
+    playfield: Res<Playfield>,
+    mut boss_query: Query<(&mut Transform, &mut Boss), (Without<Formation>, Without<BossDying>)>,
+) {
+    for (mut tf, mut boss) in boss_query.iter_mut() {
+        boss.phase_time += TIME_STEP;
+        if let BossMovement::Sweep { frequency } = PHASES[boss.phase].movement {
+            let target = Vec2::new(
+                (boss.phase_time * frequency * std::f32::consts::TAU).sin() * playfield.width / 3.0,
+                playfield.height / 4.0,
+            );
+            // ease towards the sweep so switching phases doesn't teleport the boss
+            let current = tf.translation.truncate();
+            let step = (target - current).clamp_length_max(BOSS_SPEED * 2.0 * TIME_STEP);
+            tf.translation += step.extend(0.0);
+        }
+    }
+}
+
+fn boss_fire(
+    mut commands: Commands,
+    sprite_infos: Res<SpriteInfos>,
+    hitboxes: Res<Hitboxes>,
+    mut boss_query: Query<(&Transform, &mut Boss), Without<BossDying>>,
+    player_query: Query<&Transform, (With<Player>, Without<Boss>)>,
+) {
+    let player = player_query.get_single().ok().map(|tf| tf.translation.truncate());
+    for (tf, mut boss) in boss_query.iter_mut() {
+        if !boss.fire.tick(Duration::from_secs_f32(TIME_STEP)).just_finished() {
+            continue;
+        }
+
+        let muzzle = tf.translation - Vec3::new(0.0, sprite_infos.boss.1.y / 2.0 * tf.scale.y, 0.0);
+        for attack in PHASES[boss.phase].attacks {
+            let (count, angle, aim) = match *attack {
+                BossAttack::Spread { count, angle } => (count, angle, Vec2::new(0.0, -1.0)),
+                BossAttack::Aimed { count, angle } => {
+                    let aim = player
+                        .map(|player| (player - muzzle.truncate()).normalize_or_zero())
+                        .filter(|aim| *aim != Vec2::ZERO)
+                        .unwrap_or(Vec2::new(0.0, -1.0));
+                    (count, angle, aim)
+                }
+            };
+
+            // fan `count` lasers over `angle` radians around the aim
+            for i in 0..count {
+                let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+                let direction = Vec2::new((t * angle).cos(), (t * angle).sin());
+                let direction = Vec2::new(
+                    aim.x * direction.x - aim.y * direction.y,
+                    aim.x * direction.y + aim.y * direction.x,
+                );
+                spawn_enemy_laser(&mut commands, &sprite_infos, &hitboxes, muzzle, direction);
+            }
+        }
+    }
+}
+
+fn boss_dying(
+    mut commands: Commands,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut score: ResMut<Score>,
+    mut game_rng: ResMut<GameRng>,
+    mut boss_query: Query<(Entity, &Transform, &Hitbox, &mut BossDying)>,
+    health_bars: Query<Entity, With<HealthBar>>,
+) {
+    for (entity, tf, hitbox, mut dying) in boss_query.iter_mut() {
+        if !dying.timer.tick(Duration::from_secs_f32(TIME_STEP)).just_finished() {
+            continue;
+        }
+
+        if dying.remaining > 0 {
+            // somewhere over one of the hitboxes
+            let rng = &mut game_rng.rng;
+            let (offset, size) = hitbox.0.choose(rng).map_or((Vec2::ZERO, Vec2::ZERO), Shape::bounds);
+            let jitter = Vec2::new(
+                rng.gen_range(-0.5..0.5) * size.x,
+                rng.gen_range(-0.5..0.5) * size.y,
+            );
+            let position = tf.translation + ((offset + jitter) * tf.scale.xy()).extend(1.0);
+            commands.spawn().insert(ExplosionToSpawn(position));
+            dying.remaining -= 1;
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        for bar in health_bars.iter() {
+            commands.entity(bar).despawn();
+        }
+        active_enemies.0 -= 1;
+        score.points += BOSS_POINTS;
+    }
+}
+
+fn health_bar(
+    boss_query: Query<&Boss, Changed<Boss>>,
+    mut fills: Query<(&mut Sprite, &mut Transform), With<HealthBarFill>>,
+) {
+    for boss in boss_query.iter() {
+        let fraction = boss.health as f32 / BOSS_HEALTH as f32;
+        for (mut sprite, mut tf) in fills.iter_mut() {
+            let width = HEALTH_BAR_WIDTH * fraction;
+            sprite.custom_size = Some(Vec2::new(width, HEALTH_BAR_HEIGHT));
+            // keep the bar anchored on its left end
+            tf.translation.x = -(HEALTH_BAR_WIDTH - width) / 2.0;
+        }
+    }
+}
+
+// a boss fight that ended in a game over leaves its bar behind
+fn despawn_health_bar(
+    mut commands: Commands,
+    health_bars: Query<Entity, With<HealthBar>>,
+) {
+    for bar in health_bars.iter() {
+        commands.entity(bar).despawn();
+    }
+}
@@ -0,0 +1,303 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+    },
+};
+use rand::Rng;
+
+use super::{
+    collision::LaserHitBunker,
+    hitbox::Hitbox,
+    simulation::{collision_systems, SimulationStage},
+    AppState, GameRng, Laser, LaserDirection, Playfield, RngDraw,
+};
+
+const BUNKER_COUNT: u32 = 4;
+// in mask pixels, each drawn BUNKER_SCALE screen pixels wide
+const BUNKER_WIDTH: usize = 44;
+const BUNKER_HEIGHT: usize = 32;
+const BUNKER_SCALE: f32 = 2.0;
+// how far above the bottom of the screen the bunkers stand
+const BUNKER_ELEVATION: f32 = 140.0;
+const BUNKER_COLOR: [u8; 4] = [90, 230, 110, 255];
+// mask pixels blown away around a hit, scaled up for bigger lasers
+const CRATER_RADIUS: f32 = 3.5;
+// the outer part of a crater is ragged, only some of its pixels go
+const CRATER_RAGGED: f32 = 0.6;
+
+// region:      Damage mask
+// which pixels of a bunker are still there, row 0 at the top
+struct DamageMask {
+    width: usize,
+    height: usize,
+    solid: Vec<bool>,
+}
+
+impl DamageMask {
+    // the classic shape, cut corners on top and an arch underneath
+    fn bunker() -> Self {
+        let (width, height) = (BUNKER_WIDTH, BUNKER_HEIGHT);
+        let corner = height / 4;
+        let arch_radius = width as f32 / 5.0;
+        let arch_center = Vec2::new(width as f32 / 2.0, height as f32);
+
+        let mut solid = vec![true; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let from_side = x.min(width - 1 - x);
+                let cut_corner = y < corner && from_side + y < corner;
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let arch = center.distance(arch_center) < arch_radius
+                    || (center.y > arch_center.y - arch_radius / 2.0
+                        && (center.x - arch_center.x).abs() < arch_radius);
+                solid[y * width + x] = !(cut_corner || arch);
+            }
+        }
+
+        Self { width, height, solid }
+    }
+
+    fn is_solid(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.solid[y * self.width + x]
+    }
+
+    // the first solid pixel a laser going up or down meets in the given pixel box, of those it `touches`
+    fn first_solid(
+        &self,
+        (x_min, y_min): (usize, usize),
+        (x_max, y_max): (usize, usize),
+        downwards: bool,
+        touches: impl Fn((usize, usize)) -> bool,
+    ) -> Option<(usize, usize)> {
+        let rows: Box<dyn Iterator<Item = usize>> = if downwards {
+            Box::new(y_min..=y_max)
+        } else {
+            Box::new((y_min..=y_max).rev())
+        };
+        for y in rows {
+            if let Some(x) = (x_min..=x_max).find(|&x| self.is_solid(x, y) && touches((x, y))) {
+                return Some((x, y));
+            }
+        }
+        None
+    }
+
+    fn erode(&mut self, (x, y): (usize, usize), radius: f32, rng: &mut impl Rng) {
+        let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+        let reach = radius.ceil() as isize;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let (px, py) = (x as isize + dx, y as isize + dy);
+                if px < 0 || py < 0 || px as usize >= self.width || py as usize >= self.height {
+                    continue;
+                }
+                let distance = Vec2::new(px as f32 + 0.5, py as f32 + 0.5).distance(center);
+                let gone = distance <= radius * CRATER_RAGGED
+                    || (distance <= radius && rng.gen_bool(0.5));
+                if gone {
+                    self.solid[py as usize * self.width + px as usize] = false;
+                }
+            }
+        }
+    }
+
+    fn pixels(&self) -> Vec<u8> {
+        self.solid
+            .iter()
+            .flat_map(|&solid| if solid { BUNKER_COLOR } else { [0; 4] })
+            .collect()
+    }
+}
+// endregion:   Damage mask
+
+// Component, the mask is the bunker, the texture only shows it
+#[derive(Component)]
+pub struct Bunker {
+    mask: DamageMask,
+    texture: Handle<Image>,
+}
+
+impl Bunker {
+    fn half_size(&self) -> Vec2 {
+        Vec2::new(self.mask.width as f32, self.mask.height as f32) / 2.0
+    }
+
+    // the world box a mask pixel covers
+    fn pixel_bounds(&self, tf: &Transform, (x, y): (usize, usize)) -> (Vec2, Vec2) {
+        let half = self.half_size();
+        let top_left = tf.translation.truncate() + Vec2::new(x as f32 - half.x, half.y - y as f32) * BUNKER_SCALE;
+        (top_left - Vec2::new(0.0, BUNKER_SCALE), top_left + Vec2::new(BUNKER_SCALE, 0.0))
+    }
+
+    // the mask pixels under a world box, clamped to the bunker, None when they don't overlap
+    fn pixel_box(&self, tf: &Transform, (min, max): (Vec2, Vec2)) -> Option<((usize, usize), (usize, usize))> {
+        let half = self.half_size();
+        let to_pixel = |world: Vec2| {
+            let local = (world - tf.translation.truncate()) / BUNKER_SCALE;
+            Vec2::new(local.x + half.x, half.y - local.y)
+        };
+        // y flips, the world top is the mask top row
+        let (top_left, bottom_right) = (to_pixel(Vec2::new(min.x, max.y)), to_pixel(Vec2::new(max.x, min.y)));
+        if bottom_right.x < 0.0 || bottom_right.y < 0.0 || top_left.x >= 2.0 * half.x || top_left.y >= 2.0 * half.y {
+            return None;
+        }
+        let clamp = |value: f32, size: usize| (value.max(0.0) as usize).min(size - 1);
+        Some((
+            (clamp(top_left.x, self.mask.width), clamp(top_left.y, self.mask.height)),
+            (clamp(bottom_right.x, self.mask.width), clamp(bottom_right.y, self.mask.height)),
+        ))
+    }
+}
+
+pub struct BunkerPlugin;
+
+impl Plugin for BunkerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(spawn_bunkers)
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                collision_systems()
+                    .with_system(laser_bunker_hits.label(RngDraw::BunkerCraters).after(RngDraw::PowerUpDrops))
+            )
+            .add_system(update_bunker_textures);
+    }
+}
+
+// fresh bunkers every game
+fn spawn_bunkers(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut images: ResMut<Assets<Image>>,
+    old_bunkers: Query<Entity, With<Bunker>>,
+) {
+    for entity in old_bunkers.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let y = -playfield.height / 2.0 + BUNKER_ELEVATION;
+    let spacing = playfield.width / BUNKER_COUNT as f32;
+    for i in 0..BUNKER_COUNT {
+        let mask = DamageMask::bunker();
+        let mut image = Image::new(
+            Extent3d {
+                width: mask.width as u32,
+                height: mask.height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            mask.pixels(),
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        // keep the pixels crisp when scaled up
+        image.sampler_descriptor = SamplerDescriptor {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            ..Default::default()
+        };
+        let texture = images.add(image);
+
+        let x = -playfield.width / 2.0 + spacing * (i as f32 + 0.5);
+        commands
+            .spawn_bundle(SpriteBundle {
+                texture: texture.clone(),
+                transform: Transform {
+                    translation: Vec3::new(x, y, 1.0),
+                    scale: Vec3::new(BUNKER_SCALE, BUNKER_SCALE, 1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Bunker { mask, texture });
+    }
+}
+
+// lasers from either side blow a crater where their hitbox meets the first solid pixel,
+// and only what is left of a bunker stops them. The other detection systems run after this
+// one and leave the lasers it stopped alone
+pub fn laser_bunker_hits(
+    mut hits: EventWriter<LaserHitBunker>,
+    mut game_rng: ResMut<GameRng>,
+    lasers: Query<(Entity, &Transform, &Hitbox, &LaserDirection), With<Laser>>,
+    mut bunkers: Query<(&Transform, &mut Bunker)>,
+) {
+    for (laser, laser_tf, hitbox, direction) in lasers.iter() {
+        let bounds = match hitbox.bounds(laser_tf) {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+
+        for (bunker_tf, mut bunker) in bunkers.iter_mut() {
+            let (min, max) = match bunker.pixel_box(bunker_tf, bounds) {
+                Some(pixels) => pixels,
+                None => continue,
+            };
+            let touches = |pixel| hitbox.overlaps_box(laser_tf, bunker.pixel_bounds(bunker_tf, pixel));
+            if let Some(pixel) = bunker.mask.first_solid(min, max, direction.0.y < 0.0, touches) {
+                let radius = CRATER_RADIUS * laser_tf.scale.x.abs().max(1.0);
+                bunker.mask.erode(pixel, radius, &mut game_rng.rng);
+                hits.send(LaserHitBunker { laser });
+                break;
+            }
+        }
+    }
+}
+
+// the masks are copied over to their textures, only for bunkers hit since the last frame
+fn update_bunker_textures(
+    mut images: ResMut<Assets<Image>>,
+    bunkers: Query<&Bunker, Changed<Bunker>>,
+) {
+    for bunker in bunkers.iter() {
+        if let Some(image) = images.get_mut(&bunker.texture) {
+            image.data = bunker.mask.pixels();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invaders::hitbox::Shape;
+
+    fn bunker() -> Bunker {
+        Bunker { mask: DamageMask::bunker(), texture: Handle::default() }
+    }
+
+    // the pixel a laser going down with a thin capsule from `from` to `to` stops at,
+    // for a bunker at the origin
+    fn first_hit(from: (f32, f32), to: (f32, f32)) -> Option<(usize, usize)> {
+        let (bunker, bunker_tf) = (bunker(), Transform::default());
+        let (laser_tf, hitbox) = (Transform::default(), Hitbox(vec![Shape::Capsule { from, to, radius: 1.0 }]));
+
+        let (min, max) = bunker.pixel_box(&bunker_tf, hitbox.bounds(&laser_tf)?)?;
+        let touches = |pixel| hitbox.overlaps_box(&laser_tf, bunker.pixel_bounds(&bunker_tf, pixel));
+        bunker.mask.first_solid(min, max, true, touches)
+    }
+
+    #[test]
+    fn pixel_box_is_clamped_to_the_bunker() {
+        let (bunker, tf) = (bunker(), Transform::default());
+        let whole = (Vec2::new(-1000.0, -1000.0), Vec2::new(1000.0, 1000.0));
+        assert_eq!(bunker.pixel_box(&tf, whole), Some(((0, 0), (BUNKER_WIDTH - 1, BUNKER_HEIGHT - 1))));
+
+        let beside = (Vec2::new(500.0, -10.0), Vec2::new(510.0, 10.0));
+        assert_eq!(bunker.pixel_box(&tf, beside), None);
+    }
+
+    #[test]
+    fn laser_on_the_bunker_hits() {
+        assert!(first_hit((-20.0, 20.0), (-20.0, 40.0)).is_some());
+    }
+
+    #[test]
+    fn laser_past_the_corner_misses() {
+        // the box around this slanted laser takes in solid pixels under the cut corner,
+        // the laser itself passes over it
+        assert_eq!(first_hit((-70.0, 10.0), (-30.0, 50.0)), None);
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use super::{
+    enemy::{EnemyKind, Formation},
+    bunkers::laser_bunker_hits,
+    hitbox::Hitbox,
+    powerups::{PowerUpDrop, PowerUps},
+    simulation::{collision_systems, response_systems, SimulationStage},
+    ActiveEnemies, AppState, Enemy, ExplosionToSpawn, FromEnemy, FromPlayer, Health, HitFlash,
+    Invulnerable, Laser, Lives, Player, PlayerState, Score,
+};
+
+// a little bigger than an enemy on screen
+const GRID_CELL_SIZE: f32 = 64.0;
+
+// region:      Broad phase
+// a uniform grid, things are filed under every cell their bounds touch so only
+// things sharing a cell need the exact hitbox test
+#[derive(Default)]
+pub struct SpatialGrid {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl SpatialGrid {
+    // empties the cells but keeps them around for the next step
+    pub fn clear(&mut self) {
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, (min, max): (Vec2, Vec2)) {
+        for cell in cells(min, max) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+    }
+
+    // everything filed near the bounds, each once, in the order it was inserted per cell
+    pub fn candidates(&self, (min, max): (Vec2, Vec2)) -> Vec<Entity> {
+        let mut candidates: Vec<Entity> = Vec::new();
+        for cell in cells(min, max) {
+            for &entity in self.cells.get(&cell).into_iter().flatten() {
+                if !candidates.contains(&entity) {
+                    candidates.push(entity);
+                }
+            }
+        }
+        candidates
+    }
+}
+
+fn cells(min: Vec2, max: Vec2) -> impl Iterator<Item = (i32, i32)> {
+    let cell = |value: f32| (value / GRID_CELL_SIZE).floor() as i32;
+    let (x_min, x_max, y_min, y_max) = (cell(min.x), cell(max.x), cell(min.y), cell(max.y));
+    (x_min..=x_max).flat_map(move |x| (y_min..=y_max).map(move |y| (x, y)))
+}
+// endregion:   Broad phase
+
+// region:      Events
+// Component, a laser that goes on through what it hits, hitting each enemy once
+#[derive(Component, Default)]
+pub struct Piercing {
+    hit: Vec<Entity>,
+}
+
+// a laser is sent once at most, whatever else it overlaps it is spent on this,
+// unless it is Piercing and then once per enemy
+pub struct LaserHitEnemy {
+    pub laser: Entity,
+    pub enemy: Entity,
+}
+
+pub struct LaserHitPlayer {
+    pub laser: Entity,
+    pub player: Entity,
+}
+
+// the bunker has already taken the damage by the time this is sent
+pub struct LaserHitBunker {
+    pub laser: Entity,
+}
+// endregion:   Events
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<LaserHitEnemy>()
+            .add_event::<LaserHitPlayer>()
+            .add_event::<LaserHitBunker>()
+            .add_system_set_to_stage(
+                SimulationStage,
+                collision_systems()
+                    .with_system(player_laser_hits.after(laser_bunker_hits))
+                    .with_system(enemy_laser_hits.after(laser_bunker_hits)),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                response_systems()
+                    .with_system(spend_lasers)
+                    .with_system(enemy_hit)
+                    .with_system(player_hit),
+            );
+    }
+}
+
+// region:      Detection
+// the lasers a bunker stopped this step, they hit nothing behind it
+fn stopped_by_bunkers(bunker_hits: &mut EventReader<LaserHitBunker>) -> HashSet<Entity> {
+    bunker_hits.iter().map(|hit| hit.laser).collect()
+}
+
+fn player_laser_hits(
+    mut grid: Local<SpatialGrid>,
+    mut hits: EventWriter<LaserHitEnemy>,
+    mut bunker_hits: EventReader<LaserHitBunker>,
+    mut laser_query: Query<(Entity, &Transform, &Hitbox, Option<&mut Piercing>), (With<Laser>, With<FromPlayer>)>,
+    enemy_query: Query<(Entity, &Transform, &Hitbox, Option<&Health>), With<Enemy>>,
+) {
+    grid.clear();
+    // the hits each enemy can still take this step, the boss keeps count of its own
+    let mut health: HashMap<Entity, u32> = HashMap::new();
+    for (enemy, tf, hitbox, enemy_health) in enemy_query.iter() {
+        if let Some(bounds) = hitbox.bounds(tf) {
+            grid.insert(enemy, bounds);
+        }
+        if let Some(enemy_health) = enemy_health {
+            health.insert(enemy, enemy_health.0);
+        }
+    }
+
+    let stopped = stopped_by_bunkers(&mut bunker_hits);
+    for (laser, laser_tf, laser_hitbox, mut piercing) in laser_query.iter_mut() {
+        // piercing lasers go on through bunkers too
+        if piercing.is_none() && stopped.contains(&laser) {
+            continue;
+        }
+        let bounds = match laser_hitbox.bounds(laser_tf) {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+        let touches = |&enemy: &Entity| {
+            enemy_query
+                .get(enemy)
+                .map_or(false, |(_, tf, hitbox, _)| laser_hitbox.intersects(laser_tf, hitbox, tf))
+        };
+
+        for enemy in grid.candidates(bounds).into_iter().filter(touches) {
+            // shot down by an earlier laser, this one goes on to whatever is behind
+            if health.get(&enemy) == Some(&0) {
+                continue;
+            }
+            // piercing lasers hit every enemy they haven't been through yet
+            if let Some(piercing) = piercing.as_mut() {
+                if piercing.hit.contains(&enemy) {
+                    continue;
+                }
+                piercing.hit.push(enemy);
+            }
+
+            hits.send(LaserHitEnemy { laser, enemy });
+            if let Some(health) = health.get_mut(&enemy) {
+                *health -= 1;
+            }
+            // the others are spent on the first enemy found
+            if piercing.is_none() {
+                break;
+            }
+        }
+    }
+}
+
+// there is one player at most, its bounds are broad phase enough
+fn enemy_laser_hits(
+    mut hits: EventWriter<LaserHitPlayer>,
+    mut bunker_hits: EventReader<LaserHitBunker>,
+    laser_query: Query<(Entity, &Transform, &Hitbox), (With<Laser>, With<FromEnemy>)>,
+    player_query: Query<(Entity, &Transform, &Hitbox), (With<Player>, Without<Invulnerable>)>,
+) {
+    let (player, player_tf, player_hitbox) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let (player_min, player_max) = match player_hitbox.bounds(player_tf) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    let stopped = stopped_by_bunkers(&mut bunker_hits);
+    for (laser, laser_tf, laser_hitbox) in laser_query.iter() {
+        if stopped.contains(&laser) {
+            continue;
+        }
+        let near = laser_hitbox.bounds(laser_tf).map_or(false, |(min, max)| {
+            min.cmple(player_max).all() && player_min.cmple(max).all()
+        });
+        if near && laser_hitbox.intersects(laser_tf, player_hitbox, player_tf) {
+            hits.send(LaserHitPlayer { laser, player });
+            // the player is gone, the other lasers have nothing left to hit
+            break;
+        }
+    }
+}
+// endregion:   Detection
+
+// region:      Responses
+// a laser can hit a bunker and something else in the same step, it is only despawned once
+fn spend_lasers(
+    mut commands: Commands,
+    mut enemy_hits: EventReader<LaserHitEnemy>,
+    mut player_hits: EventReader<LaserHitPlayer>,
+    mut bunker_hits: EventReader<LaserHitBunker>,
+    piercing: Query<(), With<Piercing>>,
+) {
+    let enemy_lasers = enemy_hits.iter().map(|hit| hit.laser);
+    let player_lasers = player_hits.iter().map(|hit| hit.laser);
+    let bunker_lasers = bunker_hits.iter().map(|hit| hit.laser);
+    let spent: HashSet<Entity> = enemy_lasers
+        .chain(bunker_lasers)
+        .filter(|&laser| piercing.get(laser).is_err())
+        .chain(player_lasers)
+        .collect();
+    for laser in spent {
+        commands.entity(laser).despawn();
+    }
+}
+
+// the boss takes its hits in the boss module
+fn enemy_hit(
+    mut commands: Commands,
+    mut hits: EventReader<LaserHitEnemy>,
+    mut enemy_query: Query<(&Transform, &EnemyKind, &Formation, &mut Health)>,
+    mut active_enemies: ResMut<ActiveEnemies>,
+    mut score: ResMut<Score>,
+) {
+    for hit in hits.iter() {
+        let (tf, kind, formation, mut health) = match enemy_query.get_mut(hit.enemy) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        // shot down by another laser this same step
+        if health.0 == 0 {
+            continue;
+        }
+
+        health.0 -= 1;
+        if health.0 > 0 {
+            commands.entity(hit.enemy).insert(HitFlash::default());
+            continue;
+        }
+
+        commands.entity(hit.enemy).despawn();
+        active_enemies.0 -= 1;
+        score.enemy_killed(*kind, formation);
+        commands.spawn().insert(ExplosionToSpawn(tf.translation));
+        commands.spawn().insert(PowerUpDrop(tf.translation));
+    }
+}
+
+fn player_hit(
+    mut commands: Commands,
+    mut hits: EventReader<LaserHitPlayer>,
+    player_query: Query<&Transform, With<Player>>,
+    mut power_ups: ResMut<PowerUps>,
+    mut player_state: ResMut<PlayerState>,
+    mut lives: ResMut<Lives>,
+    mut state: ResMut<State<AppState>>,
+) {
+    for hit in hits.iter() {
+        let tf = match player_query.get(hit.player) {
+            Ok(tf) => tf,
+            Err(_) => continue,
+        };
+        // the laser is spent on the shield
+        if power_ups.absorb_hit() {
+            continue;
+        }
+
+        commands.entity(hit.player).despawn();
+        player_state.shot();
+        commands.spawn().insert(ExplosionToSpawn(tf.translation));
+
+        lives.0 = lives.0.saturating_sub(1);
+        if lives.0 == 0 {
+            if let Err(e) = state.set(AppState::GameOver) {
+                warn!("Could not end the game: {:?}", e);
+            }
+        }
+    }
+}
+// endregion:   Responses
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invaders::hitbox::Shape;
+
+    fn square(size: f32) -> Hitbox {
+        Hitbox(vec![Shape::Aabb { center: (0.0, 0.0), size: (size, size) }])
+    }
+
+    fn spawn_enemy(app: &mut App, x: f32, health: u32) -> Entity {
+        app.world
+            .spawn()
+            .insert(Enemy)
+            .insert(Health(health))
+            .insert(Transform::from_xyz(x, 0.0, 0.0))
+            .insert(square(20.0))
+            .id()
+    }
+
+    fn spawn_laser(app: &mut App) -> Entity {
+        // wide enough to reach both enemies
+        app.world
+            .spawn()
+            .insert(Laser)
+            .insert(FromPlayer)
+            .insert(Transform::default())
+            .insert(square(40.0))
+            .id()
+    }
+
+    // Resource, the hits sent in the last update, as (laser, enemy)
+    #[derive(Default)]
+    struct SentHits(Vec<(Entity, Entity)>);
+
+    fn collect_hits(mut hits: EventReader<LaserHitEnemy>, mut sent: ResMut<SentHits>) {
+        sent.0 = hits.iter().map(|hit| (hit.laser, hit.enemy)).collect();
+    }
+
+    fn detection_app() -> App {
+        let mut app = App::new();
+        app
+            .add_event::<LaserHitEnemy>()
+            .add_event::<LaserHitBunker>()
+            .init_resource::<SentHits>()
+            .add_system(player_laser_hits)
+            .add_system(collect_hits.after(player_laser_hits));
+        app
+    }
+
+    fn sent_hits(app: &App) -> Vec<(Entity, Entity)> {
+        app.world.get_resource::<SentHits>().unwrap().0.clone()
+    }
+
+    #[test]
+    fn grid_finds_what_shares_a_cell() {
+        let mut grid = SpatialGrid::default();
+        let (near, far) = (Entity::from_raw(0), Entity::from_raw(1));
+        // spans two cells, it is still listed once
+        grid.insert(near, (Vec2::new(50.0, 0.0), Vec2::new(70.0, 10.0)));
+        grid.insert(far, (Vec2::new(500.0, 500.0), Vec2::new(510.0, 510.0)));
+
+        assert_eq!(grid.candidates((Vec2::new(0.0, 0.0), Vec2::new(100.0, 10.0))), vec![near]);
+        assert_eq!(grid.candidates((Vec2::new(-200.0, -200.0), Vec2::new(-190.0, -190.0))), vec![]);
+
+        grid.clear();
+        assert_eq!(grid.candidates((Vec2::new(0.0, 0.0), Vec2::new(600.0, 600.0))), vec![]);
+    }
+
+    #[test]
+    fn one_laser_hits_one_enemy() {
+        let mut app = detection_app();
+        spawn_enemy(&mut app, -10.0, 1);
+        spawn_enemy(&mut app, 10.0, 1);
+        let laser = spawn_laser(&mut app);
+
+        app.update();
+        let hits = sent_hits(&app);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, laser);
+    }
+
+    #[test]
+    fn second_laser_goes_past_a_shot_down_enemy() {
+        let mut app = detection_app();
+        spawn_enemy(&mut app, -10.0, 1);
+        spawn_enemy(&mut app, 10.0, 1);
+        spawn_laser(&mut app);
+        spawn_laser(&mut app);
+
+        app.update();
+        let hits = sent_hits(&app);
+        assert_eq!(hits.len(), 2);
+        assert_ne!(hits[0].1, hits[1].1);
+    }
+}
@@ -1,4 +1,4 @@
-use std::f32::consts::PI;
+use std::time::Duration;
 
 use bevy::{
     prelude::{
@@ -8,77 +8,224 @@ use bevy::{
         ResMut,
         Res,
         IntoSystem,
+        ParallelSystemDescriptorCoercion,
         SpriteBundle,
         Transform,
         Query,
         With,
-        Entity, Component
+        Entity, Component,
+        Time,
+        Timer,
+        Without,
+        Sprite,
+        Color,
     },
-    core::FixedTimestep,
-    math::Vec3,
+    math::{Quat, Vec2, Vec3},
 };
 use rand::{
-    thread_rng,
+    seq::SliceRandom,
     Rng,
 };
+use serde::Deserialize;
 
 use super::{
     ActiveEnemies,
-    WinSize,
+    GameRng,
+    RngDraw,
+    Playfield,
     SpriteInfos,
     Enemy,
     FromEnemy,
     Speed,
     Laser,
+    LaserDirection,
+    Player,
+    Health,
+    HitFlash,
     SCALE,
     TIME_STEP,
-    MAX_ENEMIES,
-    MAX_FORMATION_MEMBERS,
+    AppState,
+    Wave,
+    waves::{advance_wave, CurrentWave, WaveDefinition},
+    movement::{orbit_step, MovementPattern, Path},
+    simulation::{movement_systems, spawn_systems, Interpolated, SimulationStage},
+    hitbox::Hitboxes,
 };
 
+const HIT_FLASH_COLOR: Color = Color::rgb(1.0, 0.35, 0.35);
+const BURST_SHOT_INTERVAL: f32 = 0.12;
+
+// region:      Enemy kinds
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum EnemyKind {
+    Grunt,
+    Sniper,
+    Spreader,
+    Tank,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FirePattern {
+    // one laser straight down
+    Single,
+    // one laser towards the player
+    Aimed,
+    // several lasers fanned out over `angle` radians
+    Spread { count: u32, angle: f32 },
+    // several lasers straight down, one after the other
+    Burst { count: u32 },
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 4] = [EnemyKind::Grunt, EnemyKind::Sniper, EnemyKind::Spreader, EnemyKind::Tank];
+
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            EnemyKind::Grunt => "enemy_a_01.png",
+            EnemyKind::Sniper => "enemy_b_01.png",
+            EnemyKind::Spreader => "enemy_c_01.png",
+            EnemyKind::Tank => "enemy_d_01.png",
+        }
+    }
+
+    pub fn points(&self) -> u32 {
+        match self {
+            EnemyKind::Grunt => 100,
+            EnemyKind::Sniper => 150,
+            EnemyKind::Spreader => 200,
+            EnemyKind::Tank => 300,
+        }
+    }
+
+    fn health(&self) -> u32 {
+        match self {
+            EnemyKind::Grunt | EnemyKind::Sniper => 1,
+            EnemyKind::Spreader => 2,
+            EnemyKind::Tank => 4,
+        }
+    }
+
+    // relative to the wave speed
+    fn speed_factor(&self) -> f32 {
+        match self {
+            EnemyKind::Grunt => 1.0,
+            EnemyKind::Sniper => 1.25,
+            EnemyKind::Spreader => 0.9,
+            EnemyKind::Tank => 0.6,
+        }
+    }
+
+    // snipers sweep wide, tanks circle tightly
+    fn orbit_factor(&self) -> f32 {
+        match self {
+            EnemyKind::Grunt => 1.0,
+            EnemyKind::Sniper => 1.4,
+            EnemyKind::Spreader => 0.8,
+            EnemyKind::Tank => 0.5,
+        }
+    }
+
+    fn fire_pattern(&self) -> FirePattern {
+        match self {
+            EnemyKind::Grunt => FirePattern::Single,
+            EnemyKind::Sniper => FirePattern::Aimed,
+            EnemyKind::Spreader => FirePattern::Spread { count: 3, angle: 0.6 },
+            EnemyKind::Tank => FirePattern::Burst { count: 3 },
+        }
+    }
+}
+
+// Component, shots of a burst still to come
+#[derive(Component)]
+struct Burst {
+    remaining: u32,
+    timer: Timer,
+}
+// endregion:   Enemy kinds
+
 // region:      Formation
 // Component
 #[derive(Default, Clone, Component)]
-struct Formation {
+pub struct Formation {
     start: (f32, f32),
     radius: (f32, f32),
     offset: (f32, f32),
-    angle: f32,
-    group_id: u32,
+    pattern: MovementPattern,
+    // the angle on the Orbit ellipse, or the distance travelled along the other patterns
+    progress: f32,
+    member: u32,
+    pub group_id: u32,
+    pub size: u32,
+}
+
+impl Formation {
+    // a lone ellipse around `offset`, joined from wherever `start` is
+    pub fn around(start: Vec2, offset: Vec2, radius: Vec2) -> Self {
+        Self {
+            start: (start.x, start.y),
+            radius: (radius.x, radius.y),
+            offset: (offset.x, offset.y),
+            pattern: MovementPattern::Orbit,
+            progress: (start.y - offset.y).atan2(start.x - offset.x),
+            member: 0,
+            group_id: 0,
+            size: 1,
+        }
+    }
+
+    fn path(&self, playfield: &Playfield) -> Path {
+        Path {
+            offset: Vec2::new(self.offset.0, self.offset.1),
+            radius: Vec2::new(self.radius.0, self.radius.1),
+            member: self.member,
+            size: self.size,
+            dir: if self.start.0 > 0.0 { 1.0 } else { -1.0 },
+            bounds: Vec2::new(playfield.width / 2.0, playfield.height / 2.0),
+        }
+    }
 }
 
 // Resource
 #[derive(Default, Clone)]
 struct FormationMaker {
     group_seq: u32,
-    current_formation: Option<Formation>,
+    current_formation: Option<(Formation, EnemyKind)>,
     current_formation_members: u32,
 }
 
 impl FormationMaker {
-    fn make(&mut self, win_size: &WinSize) -> Formation {
+    // every member of a formation is the same kind and moves the same way, both picked from the wave
+    fn make(&mut self, playfield: &Playfield, wave: &WaveDefinition, rng: &mut impl Rng) -> (Formation, EnemyKind) {
+        let size = wave.formation_size;
         match (
             &self.current_formation,
-            self.current_formation_members >= MAX_FORMATION_MEMBERS
+            self.current_formation_members >= size
         ) {
             // if first formation or previous formation full
             (None, _) | (_, true) => {
                 // compute the start x,y
-                let mut rng = thread_rng();
-                let h_span = win_size.height / 2.0 - 100.0;
-                let w_span = win_size.width / 4.0;
+                let kind = *wave.enemies.choose(rng).unwrap_or(&EnemyKind::Grunt);
+                let pattern = *wave.patterns.choose(rng).unwrap_or(&MovementPattern::Orbit);
+                let h_span = playfield.height / 2.0 - 100.0;
+                let w_span = playfield.width / 4.0;
                 let x = if rng.gen::<bool>() {
-                    win_size.width
+                    playfield.width
                 } else {
-                    -win_size.width
+                    -playfield.width
                 };
                 let y = rng.gen_range(-h_span..h_span) as f32;
                 let start = (x, y);
 
                 // compute offset and radius
                 let offset = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
-                let radius = (rng.gen_range(80.0..150.0), 100.0);
-                let angle: f32 = (y - offset.0).atan2(x - offset.1);
+                let radius = (
+                    rng.gen_range(80.0..150.0) * kind.orbit_factor(),
+                    100.0 * kind.orbit_factor(),
+                );
+                let progress = match pattern {
+                    MovementPattern::Orbit => (y - offset.1).atan2(x - offset.0),
+                    _ => 0.0,
+                };
 
                 // create new formation
                 self.group_seq += 1;
@@ -87,19 +234,26 @@ impl FormationMaker {
                     start,
                     radius,
                     offset,
-                    angle,
+                    pattern,
+                    progress,
+                    member: 0,
                     group_id,
+                    size,
                 };
 
                 // close, set, and return
-                self.current_formation = Some(formation.clone());
+                self.current_formation = Some((formation.clone(), kind));
                 self.current_formation_members = 1;
-                formation
+                (formation, kind)
             }
             // if still within the formation count
-            (Some(formation_template), false) => {
+            (Some((formation_template, kind)), false) => {
+                let formation = Formation {
+                    member: self.current_formation_members,
+                    ..formation_template.clone()
+                };
                 self.current_formation_members += 1;
-                formation_template.clone()
+                (formation, *kind)
             }
         }
     }
@@ -113,93 +267,238 @@ impl Plugin for EnemyPlugin {
         app
             .insert_resource(FormationMaker::default())
             .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(1.0))
-                .with_system(enemy_spawn.system()),
+            SystemSet::on_enter(AppState::Playing)
+                .with_system(reset_formations.system()),
             )
+            .insert_resource(EnemyTimers::default())
             .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(0.85))
-                .with_system(enemy_fire.system()),
+            SystemSet::on_update(AppState::Playing)
+                .with_system(hit_flash.system()),
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                movement_systems()
+                    .with_system(laser_movement.system())
+                    .with_system(enemy_movement.system()),
             )
-            .add_system(laser_movement.system())
-            .add_system(enemy_movement.system());
+            .add_system_set_to_stage(
+                SimulationStage,
+                spawn_systems()
+                    .with_system(
+                        enemy_spawn.system()
+                            .label(RngDraw::EnemySpawn)
+                            .after(RngDraw::BunkerCraters)
+                            .after(advance_wave),
+                    )
+                    .with_system(enemy_fire.system())
+                    .with_system(burst_fire.system()),
+            );
+    }
+}
+
+// Resource, paced by the current wave
+struct EnemyTimers {
+    spawn: Timer,
+    fire: Timer,
+}
+impl Default for EnemyTimers {
+    fn default() -> Self {
+        Self {
+            spawn: Timer::from_seconds(1.0, true),
+            fire: Timer::from_seconds(0.85, true),
+        }
     }
 }
 
+fn reset_formations(
+    mut formation_maker: ResMut<FormationMaker>,
+    mut timers: ResMut<EnemyTimers>,
+) {
+    *formation_maker = FormationMaker::default();
+    *timers = EnemyTimers::default();
+}
+
 fn enemy_spawn(
     mut commands: Commands,
+    mut timers: ResMut<EnemyTimers>,
     mut active_enemies: ResMut<ActiveEnemies>,
     mut formation_maker: ResMut<FormationMaker>,
-    win_size: Res<WinSize>,
+    mut wave: ResMut<Wave>,
+    current_wave: Res<CurrentWave>,
+    playfield: Res<Playfield>,
     materials: Res<SpriteInfos>,
+    hitboxes: Res<Hitboxes>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    if active_enemies.0 < MAX_ENEMIES {
+    // boss waves are spawned by the boss module
+    let definition = match &current_wave.0 {
+        Some(definition) if wave.intermission.finished() && !definition.boss => definition,
+        _ => return,
+    };
+
+    timers.spawn.set_duration(Duration::from_secs_f32(definition.spawn_interval));
+    if !timers.spawn.tick(Duration::from_secs_f32(TIME_STEP)).just_finished() {
+        return;
+    }
+
+    if wave.spawned < definition.enemy_count && active_enemies.0 < definition.max_on_screen {
         // get the formation and start x,y
-        let formation = formation_maker.make(&win_size);
+        let (formation, kind) = formation_maker.make(&playfield, definition, &mut game_rng.rng);
         let (x, y) = formation.start;
+        let translation = Vec3::new(x, y, 10.0);
 
         // spawn enemy
         commands
             .spawn_bundle(SpriteBundle {
-                texture: materials.enemy.0.clone(),
+                texture: materials.enemies[&kind].0.clone(),
                 transform: Transform {
-                    translation: Vec3::new(x, y, 10.0),
+                    translation,
                     scale: Vec3::new(SCALE, SCALE, SCALE),
                     ..Default::default()
                 },
                 ..Default::default()
             })
             .insert(Enemy)
-            .insert(Speed::default())
-            .insert(formation);
+            .insert(kind)
+            .insert(Health(kind.health()))
+            .insert(Speed(definition.speed * kind.speed_factor()))
+            .insert(formation)
+            .insert(hitboxes.enemies[&kind].clone())
+            .insert(Interpolated::at(translation));
         
         active_enemies.0 += 1;
+        wave.spawned += 1;
     }
 }
 
+pub fn spawn_enemy_laser(
+    commands: &mut Commands,
+    materials: &SpriteInfos,
+    hitboxes: &Hitboxes,
+    from: Vec3,
+    direction: Vec2,
+) {
+    // the sprite points down, turn it towards where it's headed
+    let angle = direction.x.atan2(-direction.y);
+    let translation = Vec3::new(from.x, from.y - 15.0, 0.0);
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: materials.enemy_laser.0.clone(),
+            transform: Transform {
+                translation,
+                rotation: Quat::from_rotation_z(angle),
+                scale: Vec3::new(SCALE, -SCALE, 1.0),
+            },
+            ..Default::default()
+        })
+        .insert(Laser)
+        .insert(FromEnemy)
+        .insert(LaserDirection(direction))
+        .insert(Speed::default())
+        .insert(hitboxes.enemy_laser.clone())
+        .insert(Interpolated::at(translation));
+}
+
 fn enemy_fire(
     mut commands: Commands,
+    mut timers: ResMut<EnemyTimers>,
+    current_wave: Res<CurrentWave>,
     materials: Res<SpriteInfos>,
-    enemy_query: Query<&Transform, With<Enemy>>,
+    hitboxes: Res<Hitboxes>,
+    enemy_query: Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
 ) {
-    for &tf in enemy_query.iter() {
-        let x = tf.translation.x;
-        let y = tf.translation.y;
+    if let Some(definition) = &current_wave.0 {
+        timers.fire.set_duration(Duration::from_secs_f32(definition.fire_interval));
+    }
+    if !timers.fire.tick(Duration::from_secs_f32(TIME_STEP)).just_finished() {
+        return;
+    }
 
-        // spawn enemy laser sprite
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: materials.enemy_laser.0.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y - 15.0, 0.0),
-                    scale: Vec3::new(SCALE, -SCALE, 1.0),
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Laser)
-            .insert(FromEnemy)
-            .insert(Speed::default());
+    let player = player_query.get_single().ok().map(|tf| tf.translation.truncate());
+    for (entity, tf, kind) in enemy_query.iter() {
+        let down = Vec2::new(0.0, -1.0);
+        match kind.fire_pattern() {
+            FirePattern::Single => spawn_enemy_laser(&mut commands, &materials, &hitboxes, tf.translation, down),
+            FirePattern::Aimed => {
+                // straight down when there is no player to aim at
+                let direction = player
+                    .map(|player| (player - tf.translation.truncate()).normalize_or_zero())
+                    .filter(|direction| *direction != Vec2::ZERO)
+                    .unwrap_or(down);
+                spawn_enemy_laser(&mut commands, &materials, &hitboxes, tf.translation, direction);
+            }
+            FirePattern::Spread { count, angle } => {
+                for i in 0..count {
+                    let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+                    let direction = Vec2::new((t * angle).sin(), -(t * angle).cos());
+                    spawn_enemy_laser(&mut commands, &materials, &hitboxes, tf.translation, direction);
+                }
+            }
+            FirePattern::Burst { count } => {
+                spawn_enemy_laser(&mut commands, &materials, &hitboxes, tf.translation, down);
+                commands.entity(entity).insert(Burst {
+                    remaining: count.saturating_sub(1),
+                    timer: Timer::from_seconds(BURST_SHOT_INTERVAL, true),
+                });
+            }
+        }
     }
 }
 
+fn burst_fire(
+    mut commands: Commands,
+    materials: Res<SpriteInfos>,
+    hitboxes: Res<Hitboxes>,
+    mut query: Query<(Entity, &Transform, &mut Burst), With<Enemy>>,
+) {
+    for (entity, tf, mut burst) in query.iter_mut() {
+        if burst.remaining == 0 {
+            commands.entity(entity).remove::<Burst>();
+            continue;
+        }
+
+        if burst.timer.tick(Duration::from_secs_f32(TIME_STEP)).just_finished() {
+            spawn_enemy_laser(&mut commands, &materials, &hitboxes, tf.translation, Vec2::new(0.0, -1.0));
+            burst.remaining -= 1;
+        }
+    }
+}
+
+fn hit_flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Sprite, &mut HitFlash)>,
+) {
+    for (entity, mut sprite, mut flash) in query.iter_mut() {
+        if flash.0.tick(time.delta()).finished() {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<HitFlash>();
+        } else {
+            sprite.color = HIT_FLASH_COLOR;
+        }
+    }
+}
 
 fn laser_movement(
     mut commands: Commands,
-    win_size: Res<WinSize>,
-    mut query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromEnemy>)>
+    playfield: Res<Playfield>,
+    mut query: Query<(Entity, &Speed, &LaserDirection, &mut Transform), (With<Laser>, With<FromEnemy>)>
 ) {
-    for (laser_entity, speed, mut laser_tf) in query.iter_mut() {
+    for (laser_entity, speed, direction, mut laser_tf) in query.iter_mut() {
         let translation = &mut laser_tf.translation;
-        translation.y -= speed.0 * TIME_STEP;
-        if translation.y < -win_size.height / 2.0 - 50.0 {
+        translation.x += direction.0.x * speed.0 * TIME_STEP;
+        translation.y += direction.0.y * speed.0 * TIME_STEP;
+        if translation.y < -playfield.height / 2.0 - 50.0
+            || translation.x.abs() > playfield.width / 2.0 + 50.0
+        {
             commands.entity(laser_entity).despawn();
         }
     }
 }
 
 fn enemy_movement(
+    playfield: Res<Playfield>,
     mut query: Query<(&mut Transform, &Speed, &mut Formation), With<Enemy>>
 ) {
     for (mut transform, speed, mut formation) in query.iter_mut() {
@@ -207,18 +506,23 @@ fn enemy_movement(
         let x_org = transform.translation.x;
         let y_org = transform.translation.y;
 
-        // Get the ellipse shape
-        let (x_offset, y_offset) = formation.offset;
-        let (x_radius, y_radius) = formation.radius;
-
-        // Compute the next angle
-        let dir = if formation.start.0 > 0.0 { 1.0 } else { -1.0 };
-        let angle = formation.angle + dir * speed.0 * TIME_STEP / (x_radius.min(y_radius) * PI / 2.0);
-
-        // Calculate the destination
-        let x_dst = x_radius * angle.cos() + x_offset;
-        let y_dst = y_radius * angle.sin() + y_offset;
+        // Compute the next step along the pattern, and the destination there
+        let (progress, x_dst, y_dst) = match formation.pattern {
+            MovementPattern::Orbit => {
+                // Get the ellipse shape
+                let (x_offset, y_offset) = formation.offset;
+                let (x_radius, y_radius) = formation.radius;
 
+                let dir = if formation.start.0 > 0.0 { 1.0 } else { -1.0 };
+                let angle = formation.progress + dir * orbit_step(Vec2::new(x_radius, y_radius), max_distance);
+                (angle, x_radius * angle.cos() + x_offset, y_radius * angle.sin() + y_offset)
+            }
+            pattern => {
+                let distance = formation.progress + max_distance;
+                let destination = pattern.position(&formation.path(&playfield), distance);
+                (distance, destination.x, destination.y)
+            }
+        };
 
         let dx = x_org - x_dst;
         let dy = y_org - y_dst;
@@ -229,7 +533,7 @@ fn enemy_movement(
             max_distance / distance
         };
 
-        // Calculate the final x,y (make sure to not overshoot the intended ellipse path)
+        // Calculate the final x,y (make sure to not overshoot the intended path)
         let x = x_org - dx * distance_ratio;
         let x = if dx > 0.0 {
             x.max(x_dst)
@@ -244,9 +548,9 @@ fn enemy_movement(
             y.min(y_dst)
         };
 
-        // start rotating the formation anlge only when the sprite is on or close to the destination
+        // only move along the pattern when the sprite is on or close to the destination
         if distance < max_distance * speed.0 / 20.0 {
-            formation.angle = angle;
+            formation.progress = progress;
         }
 
         // Apply the translation
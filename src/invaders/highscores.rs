@@ -0,0 +1,233 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_path;
+use crate::widgets::{TextInput, WidgetAction, WidgetEvent, WidgetValue};
+
+use super::Score;
+
+const SAVE_FILE: &str = "invaders_scores.ron";
+// bump when the file layout changes, older files are then set aside instead of misread
+const HIGH_SCORE_VERSION: u32 = 1;
+const MAX_ENTRIES: usize = 10;
+const MAX_INITIALS: usize = 3;
+
+pub const SUBMIT_INITIALS: WidgetAction = WidgetAction("invaders.submit_initials");
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub initials: String,
+    pub points: u32,
+}
+
+// what is written to disk
+#[derive(Serialize, Deserialize)]
+struct HighScoreFile {
+    version: u32,
+    entries: Vec<HighScoreEntry>,
+}
+
+// Resource, best first
+#[derive(Default)]
+pub struct HighScores(pub Vec<HighScoreEntry>);
+
+impl HighScores {
+    fn load() -> Self {
+        match data_path(SAVE_FILE) {
+            Some(path) => Self::load_from(&path),
+            None => Self::default(),
+        }
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            // no file yet is the normal first run
+            Err(_) => return Self::default(),
+        };
+
+        match ron::from_str::<HighScoreFile>(&contents) {
+            Ok(file) if file.version == HIGH_SCORE_VERSION => {
+                let mut high_scores = Self(file.entries);
+                high_scores.0.sort_by(|a, b| b.points.cmp(&a.points));
+                high_scores.0.truncate(MAX_ENTRIES);
+                high_scores
+            }
+            Ok(file) => {
+                warn!("High scores in {} have unknown version {}, starting a new table", path.display(), file.version);
+                set_aside(path);
+                Self::default()
+            }
+            Err(e) => {
+                warn!("High scores in {} are unreadable ({}), starting a new table", path.display(), e);
+                set_aside(path);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        match data_path(SAVE_FILE) {
+            Some(path) => self.save_to(&path),
+            None => warn!("No data directory available, high scores not saved"),
+        }
+    }
+
+    fn save_to(&self, path: &Path) {
+        let file = HighScoreFile {
+            version: HIGH_SCORE_VERSION,
+            entries: self.0.clone(),
+        };
+        let result = ron::ser::to_string_pretty(&file, Default::default())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .and_then(|contents| {
+                path.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(path, contents))
+            });
+        if let Err(e) = result {
+            warn!("Could not save high scores to {}: {}", path.display(), e);
+        }
+    }
+
+    pub fn qualifies(&self, points: u32) -> bool {
+        points > 0 && (self.0.len() < MAX_ENTRIES || self.0.iter().any(|entry| points > entry.points))
+    }
+
+    fn insert(&mut self, entry: HighScoreEntry) {
+        // ties go below the existing entries
+        let index = self.0
+            .iter()
+            .position(|existing| entry.points > existing.points)
+            .unwrap_or(self.0.len());
+        self.0.insert(index, entry);
+        self.0.truncate(MAX_ENTRIES);
+    }
+}
+
+// keep a bad file around for inspection rather than overwriting it on the next save
+fn set_aside(path: &Path) {
+    let backup = path.with_extension("ron.bad");
+    if let Err(e) = fs::rename(path, &backup) {
+        warn!("Could not move {} aside: {}", path.display(), e);
+    }
+}
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(HighScores::load())
+            .add_system(submit_initials);
+    }
+}
+
+fn submit_initials(
+    mut commands: Commands,
+    mut widget_events: EventReader<WidgetEvent>,
+    score: Res<Score>,
+    mut high_scores: ResMut<HighScores>,
+    inputs: Query<(Entity, &WidgetAction), With<TextInput>>,
+) {
+    let mut submitted = false;
+    for event in widget_events.iter() {
+        // one entry per game, a second submit in the same frame is dropped
+        if submitted {
+            continue;
+        }
+        let initials = match (event.action, &event.value) {
+            (SUBMIT_INITIALS, WidgetValue::Text(initials)) => initials,
+            _ => continue,
+        };
+
+        let initials: String = initials
+            .trim()
+            .chars()
+            .take(MAX_INITIALS)
+            .collect::<String>()
+            .to_uppercase();
+        if initials.is_empty() {
+            continue;
+        }
+
+        high_scores.insert(HighScoreEntry { initials, points: score.points });
+        high_scores.save();
+        submitted = true;
+
+        // and no more submits after it
+        for (entity, action) in inputs.iter() {
+            if *action == SUBMIT_INITIALS {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // a fresh directory for each test, under the system temp dir
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bevytuts-high-scores-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(initials: &str, points: u32) -> HighScoreEntry {
+        HighScoreEntry {
+            initials: initials.to_string(),
+            points,
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_table() {
+        let path = scratch_dir("missing").join(SAVE_FILE);
+        assert!(HighScores::load_from(&path).0.is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_is_set_aside() {
+        let path = scratch_dir("unreadable").join(SAVE_FILE);
+        fs::write(&path, "not ron at all").unwrap();
+
+        assert!(HighScores::load_from(&path).0.is_empty());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(path.with_extension("ron.bad")).unwrap(), "not ron at all");
+    }
+
+    #[test]
+    fn other_version_is_set_aside() {
+        let path = scratch_dir("version").join(SAVE_FILE);
+        let file = HighScoreFile {
+            version: HIGH_SCORE_VERSION + 1,
+            entries: vec![entry("AAA", 100)],
+        };
+        fs::write(&path, ron::to_string(&file).unwrap()).unwrap();
+
+        assert!(HighScores::load_from(&path).0.is_empty());
+        assert!(!path.exists());
+        assert!(path.with_extension("ron.bad").exists());
+    }
+
+    #[test]
+    fn saved_table_loads_back() {
+        let path = scratch_dir("round_trip").join("nested").join(SAVE_FILE);
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry("AAA", 100));
+        high_scores.insert(entry("BBB", 300));
+        high_scores.insert(entry("CCC", 200));
+        high_scores.save_to(&path);
+
+        let loaded = HighScores::load_from(&path);
+        let loaded: Vec<_> = loaded.0.iter().map(|entry| (entry.initials.as_str(), entry.points)).collect();
+        assert_eq!(loaded, [("BBB", 300), ("CCC", 200), ("AAA", 100)]);
+    }
+}
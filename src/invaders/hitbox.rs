@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    f32::consts::TAU,
+};
+
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    math::Vec3Swizzles,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::input_map::{Action, ActionState};
+
+use super::{
+    EnemyKind, SpriteInfos, BOSS_SPRITE, ENEMY_LASER_SPRITE, PLAYER_LASER_SPRITE,
+    PLAYER_SPRITE,
+};
+
+const HITBOX_FILE: &str = "hitboxes/invaders.hitboxes.ron";
+const OUTLINE_COLOR: Color = Color::rgba(0.2, 1.0, 0.3, 0.9);
+const OUTLINE_WIDTH: f32 = 1.5;
+const OUTLINE_Z: f32 = 50.0;
+const CIRCLE_SEGMENTS: usize = 16;
+
+// region:      Shapes
+// in sprite pixels relative to the sprite centre, y up
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Shape {
+    Circle { center: (f32, f32), radius: f32 },
+    // every point within `radius` of the line from `from` to `to`
+    Capsule { from: (f32, f32), to: (f32, f32), radius: f32 },
+    // stays axis aligned whatever the sprite rotation, use capsules for things that turn
+    Aabb { center: (f32, f32), size: (f32, f32) },
+}
+
+// a shape moved into the world by an entity transform
+#[derive(Debug, Clone, Copy)]
+enum Placed {
+    Capsule { from: Vec2, to: Vec2, radius: f32 },
+    Aabb { min: Vec2, max: Vec2 },
+}
+
+impl Shape {
+    // centre and size of the box around the shape, in sprite pixels
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match *self {
+            Shape::Circle { center: (x, y), radius } => (Vec2::new(x, y), Vec2::splat(2.0 * radius)),
+            Shape::Capsule { from, to, radius } => {
+                let (from, to) = (Vec2::new(from.0, from.1), Vec2::new(to.0, to.1));
+                ((from + to) / 2.0, (to - from).abs() + Vec2::splat(2.0 * radius))
+            }
+            Shape::Aabb { center: (x, y), size: (width, height) } => (Vec2::new(x, y), Vec2::new(width, height)),
+        }
+    }
+
+    fn place(&self, tf: &Transform) -> Placed {
+        let point = |(x, y): (f32, f32)| tf.mul_vec3(Vec3::new(x, y, 0.0)).truncate();
+        let scale = tf.scale.xy().abs();
+        match *self {
+            Shape::Circle { center, radius } => Placed::Capsule {
+                from: point(center),
+                to: point(center),
+                radius: radius * scale.max_element(),
+            },
+            Shape::Capsule { from, to, radius } => Placed::Capsule {
+                from: point(from),
+                to: point(to),
+                radius: radius * scale.max_element(),
+            },
+            Shape::Aabb { center, size: (width, height) } => {
+                let half = Vec2::new(width, height) * scale / 2.0;
+                let center = point(center);
+                Placed::Aabb { min: center - half, max: center + half }
+            }
+        }
+    }
+}
+
+impl Placed {
+    fn intersects(&self, other: &Placed) -> bool {
+        match (*self, *other) {
+            (Placed::Capsule { from: a0, to: a1, radius: ra }, Placed::Capsule { from: b0, to: b1, radius: rb }) => {
+                segment_distance(a0, a1, b0, b1) <= ra + rb
+            }
+            (Placed::Capsule { from, to, radius }, Placed::Aabb { min, max })
+            | (Placed::Aabb { min, max }, Placed::Capsule { from, to, radius }) => {
+                segment_box_distance(from, to, min, max) <= radius
+            }
+            (Placed::Aabb { min: a_min, max: a_max }, Placed::Aabb { min: b_min, max: b_max }) => {
+                a_min.x <= b_max.x && b_min.x <= a_max.x && a_min.y <= b_max.y && b_min.y <= a_max.y
+            }
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        match *self {
+            Placed::Capsule { from, to, radius } => (from.min(to) - Vec2::splat(radius), from.max(to) + Vec2::splat(radius)),
+            Placed::Aabb { min, max } => (min, max),
+        }
+    }
+
+    // the outline as line segments, for drawing
+    fn outline(&self) -> Vec<(Vec2, Vec2)> {
+        match *self {
+            Placed::Capsule { from, to, radius } => {
+                let mut lines = circle(from, radius);
+                if from != to {
+                    lines.extend(circle(to, radius));
+                    let side = (to - from).normalize().perp() * radius;
+                    lines.push((from + side, to + side));
+                    lines.push((from - side, to - side));
+                }
+                lines
+            }
+            Placed::Aabb { min, max } => {
+                let (top_left, bottom_right) = (Vec2::new(min.x, max.y), Vec2::new(max.x, min.y));
+                vec![(min, top_left), (top_left, max), (max, bottom_right), (bottom_right, min)]
+            }
+        }
+    }
+}
+
+fn circle(center: Vec2, radius: f32) -> Vec<(Vec2, Vec2)> {
+    let point = |i: usize| {
+        let angle = i as f32 * TAU / CIRCLE_SEGMENTS as f32;
+        center + Vec2::new(angle.cos(), angle.sin()) * radius
+    };
+    (0..CIRCLE_SEGMENTS).map(|i| (point(i), point(i + 1))).collect()
+}
+
+fn closest_on_segment(point: Vec2, from: Vec2, to: Vec2) -> Vec2 {
+    let line = to - from;
+    let length_squared = line.length_squared();
+    if length_squared == 0.0 {
+        return from;
+    }
+    from + line * ((point - from).dot(line) / length_squared).clamp(0.0, 1.0)
+}
+
+fn segments_cross(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> bool {
+    let side = |p: Vec2, from: Vec2, to: Vec2| (to - from).perp_dot(p - from);
+    let (d0, d1) = (side(b0, a0, a1), side(b1, a0, a1));
+    let (d2, d3) = (side(a0, b0, b1), side(a1, b0, b1));
+    d0 * d1 < 0.0 && d2 * d3 < 0.0
+}
+
+// two segments that don't cross are closest at an end of one of them
+fn segment_distance(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> f32 {
+    if segments_cross(a0, a1, b0, b1) {
+        return 0.0;
+    }
+    [
+        a0.distance(closest_on_segment(a0, b0, b1)),
+        a1.distance(closest_on_segment(a1, b0, b1)),
+        b0.distance(closest_on_segment(b0, a0, a1)),
+        b1.distance(closest_on_segment(b1, a0, a1)),
+    ]
+    .iter()
+    .fold(f32::MAX, |closest, &distance| closest.min(distance))
+}
+
+// same idea, a segment outside a box is closest at one of its ends or one of the box corners
+fn segment_box_distance(from: Vec2, to: Vec2, min: Vec2, max: Vec2) -> f32 {
+    let corners = [min, Vec2::new(min.x, max.y), max, Vec2::new(max.x, min.y)];
+    let inside = |p: Vec2| p.cmpge(min).all() && p.cmple(max).all();
+    let crosses_edge = corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .any(|(&corner, &next)| segments_cross(from, to, corner, next));
+    if inside(from) || inside(to) || crosses_edge {
+        return 0.0;
+    }
+
+    let to_box = |p: Vec2| p.distance(p.clamp(min, max));
+    corners
+        .iter()
+        .map(|&corner| corner.distance(closest_on_segment(corner, from, to)))
+        .fold(to_box(from).min(to_box(to)), f32::min)
+}
+// endregion:   Shapes
+
+// region:      Hitbox file asset
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "3c5e8a1d-94b2-4f6e-8d07-2a9f6b1e4c53"]
+pub struct HitboxFile {
+    // by sprite file
+    shapes: HashMap<String, Vec<Shape>>,
+    // sprites drawn over the same outline as another, sharing its shapes
+    #[serde(default)]
+    same_as: HashMap<String, String>,
+}
+
+impl HitboxFile {
+    fn shapes(&self, sprite: &str) -> Option<&Vec<Shape>> {
+        let sprite = self.same_as.get(sprite).map_or(sprite, String::as_str);
+        self.shapes.get(sprite)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (sprite, other) in self.same_as.iter() {
+            if !self.shapes.contains_key(other) {
+                return Err(format!("{} is the same as {}, which has no shapes", sprite, other));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct HitboxFileLoader;
+
+impl AssetLoader for HitboxFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file: HitboxFile = ron::de::from_bytes(bytes)?;
+            // a bad edit keeps the hitboxes already loaded
+            if let Err(problem) = file.validate() {
+                warn!("Rejecting {}: {}", load_context.path().display(), problem);
+                return Err(anyhow::anyhow!(problem));
+            }
+            load_context.set_default_asset(LoadedAsset::new(file));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["hitboxes.ron"]
+    }
+}
+
+// Resource
+pub struct HitboxFileHandle(pub Handle<HitboxFile>);
+// endregion:   Hitbox file asset
+
+// region:      Hitboxes
+// Component, what of an entity can be hit
+#[derive(Component, Debug, Clone, Default)]
+pub struct Hitbox(pub Vec<Shape>);
+
+impl Hitbox {
+    // the whole sprite, for sprites the hitbox file doesn't cover
+    fn sprite(size: Vec2) -> Self {
+        Self(vec![Shape::Aabb { center: (0.0, 0.0), size: (size.x, size.y) }])
+    }
+
+    // the world box around all of the shapes, None without any
+    pub fn bounds(&self, tf: &Transform) -> Option<(Vec2, Vec2)> {
+        self.0
+            .iter()
+            .map(|shape| shape.place(tf).bounds())
+            .reduce(|(min, max), (shape_min, shape_max)| (min.min(shape_min), max.max(shape_max)))
+    }
+
+    // whether any of the shapes reaches into a world box
+    pub fn overlaps_box(&self, tf: &Transform, (min, max): (Vec2, Vec2)) -> bool {
+        let area = Placed::Aabb { min, max };
+        self.0.iter().any(|shape| shape.place(tf).intersects(&area))
+    }
+
+    pub fn intersects(&self, tf: &Transform, other: &Hitbox, other_tf: &Transform) -> bool {
+        self.0.iter().any(|shape| {
+            let placed = shape.place(tf);
+            other.0.iter().any(|other_shape| placed.intersects(&other_shape.place(other_tf)))
+        })
+    }
+}
+
+// Resource, the hitbox of every sprite, read along with the sprites
+pub struct Hitboxes {
+    pub player: Hitbox,
+    pub player_laser: Hitbox,
+    pub enemies: HashMap<EnemyKind, Hitbox>,
+    pub enemy_laser: Hitbox,
+    pub boss: Hitbox,
+}
+
+impl Hitboxes {
+    // without the file every sprite is hit anywhere on its image
+    pub fn new(sprite_infos: &SpriteInfos, file: Option<&HitboxFile>) -> Self {
+        let hitbox = |sprite: &str, size: Vec2| {
+            file.and_then(|file| file.shapes(sprite))
+                .map(|shapes| Hitbox(shapes.clone()))
+                .unwrap_or_else(|| Hitbox::sprite(size))
+        };
+
+        Self {
+            player: hitbox(PLAYER_SPRITE, sprite_infos.player.1),
+            player_laser: hitbox(PLAYER_LASER_SPRITE, sprite_infos.player_laser.1),
+            enemies: EnemyKind::ALL
+                .iter()
+                .map(|&kind| (kind, hitbox(kind.sprite(), sprite_infos.enemies[&kind].1)))
+                .collect(),
+            enemy_laser: hitbox(ENEMY_LASER_SPRITE, sprite_infos.enemy_laser.1),
+            boss: hitbox(BOSS_SPRITE, sprite_infos.boss.1),
+        }
+    }
+}
+// endregion:   Hitboxes
+
+// region:      Debug drawing
+// Resource
+#[derive(Default)]
+struct ShowHitboxes(bool);
+
+#[derive(Component)]
+struct HitboxOutline;
+
+pub struct HitboxPlugin;
+
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<HitboxFile>()
+            .init_asset_loader::<HitboxFileLoader>()
+            .init_resource::<ShowHitboxes>()
+            .add_startup_system(setup)
+            .add_system(reload_hitboxes)
+            .add_system(toggle_hitboxes)
+            .add_system(draw_hitboxes.after(toggle_hitboxes));
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(HitboxFileHandle(asset_server.load(HITBOX_FILE)));
+}
+
+// the file can be edited while the game runs, entities already spawned keep the hitbox they were given
+fn reload_hitboxes(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<HitboxFile>>,
+    handle: Res<HitboxFileHandle>,
+    files: Res<Assets<HitboxFile>>,
+    sprite_infos: Option<Res<SpriteInfos>>,
+) {
+    let modified = events
+        .iter()
+        .filter(|event| matches!(event, AssetEvent::Modified { handle: modified } if *modified == handle.0))
+        .count() > 0;
+    // until loading is over there are no sprite sizes yet, the loading screen builds them then
+    if let (true, Some(sprite_infos)) = (modified, sprite_infos) {
+        info!("Invaders hitboxes reloaded");
+        commands.insert_resource(Hitboxes::new(&sprite_infos, files.get(&handle.0)));
+    }
+}
+
+fn toggle_hitboxes(
+    actions: Res<ActionState>,
+    mut show: ResMut<ShowHitboxes>,
+) {
+    if actions.just_pressed(Action::ToggleHitboxes) {
+        show.0 = !show.0;
+    }
+}
+
+// the outlines are redrawn every frame, from where the collision systems see the shapes
+fn draw_hitboxes(
+    mut commands: Commands,
+    show: Res<ShowHitboxes>,
+    hitboxes: Query<(&Transform, &Hitbox)>,
+    outlines: Query<Entity, With<HitboxOutline>>,
+) {
+    for entity in outlines.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !show.0 {
+        return;
+    }
+
+    for (tf, hitbox) in hitboxes.iter() {
+        for shape in hitbox.0.iter() {
+            for (from, to) in shape.place(tf).outline() {
+                let line = to - from;
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: OUTLINE_COLOR,
+                            custom_size: Some(Vec2::new(line.length(), OUTLINE_WIDTH)),
+                            ..Default::default()
+                        },
+                        transform: Transform {
+                            translation: ((from + to) / 2.0).extend(OUTLINE_Z),
+                            rotation: Quat::from_rotation_z(line.y.atan2(line.x)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(HitboxOutline);
+            }
+        }
+    }
+}
+// endregion:   Debug drawing
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_file_loads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/hitboxes/invaders.hitboxes.ron");
+        let file: HitboxFile = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(file.validate(), Ok(()));
+
+        // every enemy is covered, the recoloured ones through same_as
+        let grunt = file.shapes(EnemyKind::Grunt.sprite()).unwrap();
+        for kind in EnemyKind::ALL {
+            assert_eq!(file.shapes(kind.sprite()).unwrap().len(), grunt.len());
+        }
+    }
+
+    #[test]
+    fn same_as_must_point_at_shapes() {
+        let file: HitboxFile = ron::from_str(r#"(shapes: {}, same_as: { "b.png": "a.png" })"#).unwrap();
+        assert!(file.validate().is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+
+use crate::widgets::spawn_label;
+
+use super::{
+    player::{FireSettings, Weapon},
+    powerups::PowerUps,
+    waves::CurrentWave,
+    AppState, Lives, Player, PlayerState, Score, Wave,
+};
+
+const HUD_FONT_SIZE: f32 = 20.0;
+const BANNER_FONT_SIZE: f32 = 48.0;
+// blocks in the charge meter
+const CHARGE_METER: usize = 5;
+
+#[derive(Component)]
+struct Hud;
+
+// the fire mode, and the charge meter while charging
+#[derive(Component)]
+struct FireText;
+
+// the centre of the screen, for the wave number and the respawn countdown
+#[derive(Component)]
+struct Banner;
+
+#[derive(Component)]
+enum HudText {
+    Score,
+    Lives,
+    Wave,
+    PowerUps,
+}
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(setup)
+            .add_system(hud_visibility)
+            .add_system(update_hud)
+            .add_system(fire_text)
+            .add_system(banner);
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(30.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    bottom: Val::Px(0.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(10.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                display: Display::None,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|parent| {
+            spawn_label(parent, "", HUD_FONT_SIZE).insert(HudText::Score);
+            spawn_label(parent, "", HUD_FONT_SIZE).insert(HudText::Wave);
+            spawn_label(parent, "", HUD_FONT_SIZE).insert(HudText::PowerUps);
+            spawn_label(parent, "", HUD_FONT_SIZE).insert(FireText);
+            spawn_label(parent, "", HUD_FONT_SIZE).insert(HudText::Lives);
+        });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                display: Display::None,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Banner)
+        .with_children(|parent| {
+            spawn_label(parent, "", BANNER_FONT_SIZE);
+        });
+}
+
+// the hud stays up behind the pause and game over screens, only the title and loading hide it
+fn hud_visibility(
+    state: Res<State<AppState>>,
+    mut hud: Query<&mut Style, With<Hud>>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+
+    for mut style in hud.iter_mut() {
+        style.display = match state.current() {
+            AppState::Loading | AppState::MissingAssets | AppState::Title => Display::None,
+            _ => Display::Flex,
+        };
+    }
+}
+
+fn update_hud(
+    score: Res<Score>,
+    lives: Res<Lives>,
+    wave: Res<Wave>,
+    power_ups: Res<PowerUps>,
+    mut texts: Query<(&mut Text, &HudText)>,
+) {
+    if !score.is_changed() && !lives.is_changed() && !wave.is_changed() && !power_ups.is_changed() {
+        return;
+    }
+
+    for (mut text, hud_text) in texts.iter_mut() {
+        text.sections[0].value = match hud_text {
+            HudText::Score => format!("SCORE {:06}", score.points),
+            HudText::Lives => format!("LIVES {}", lives.0),
+            HudText::Wave => format!("WAVE {}", wave.number),
+            // "SPREAD x2 7s  SHIELD 12s"
+            HudText::PowerUps => power_ups
+                .remaining()
+                .iter()
+                .map(|(kind, stacks, seconds)| match stacks {
+                    1 => format!("{} {}s", kind.name(), seconds.ceil() as u32),
+                    _ => format!("{} x{} {}s", kind.name(), stacks, seconds.ceil() as u32),
+                })
+                .collect::<Vec<_>>()
+                .join("  "),
+        };
+    }
+}
+
+fn fire_text(
+    settings: Res<FireSettings>,
+    weapons: Query<&Weapon, With<Player>>,
+    mut texts: Query<&mut Text, With<FireText>>,
+) {
+    let value = if settings.autofire {
+        "AUTO".to_string()
+    } else {
+        let charge = weapons.get_single().map_or(0.0, |weapon| weapon.charge());
+        let blocks = (charge * CHARGE_METER as f32) as usize;
+        match blocks {
+            0 => "TAP".to_string(),
+            _ => format!("TAP {}{}", "#".repeat(blocks), ".".repeat(CHARGE_METER - blocks)),
+        }
+    };
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+// "WAVE N" shows for as long as the wave intermission runs, then the respawn countdown if the player is gone
+fn banner(
+    state: Res<State<AppState>>,
+    wave: Res<Wave>,
+    current_wave: Res<CurrentWave>,
+    player_state: Res<PlayerState>,
+    mut banners: Query<(&mut Style, &Children), With<Banner>>,
+    mut texts: Query<&mut Text>,
+) {
+    let value = if !wave.intermission.finished() {
+        let boss = current_wave.0.as_ref().map_or(false, |definition| definition.boss);
+        if boss {
+            Some(format!("WAVE {}\nBOSS", wave.number))
+        } else {
+            Some(format!("WAVE {}", wave.number))
+        }
+    } else {
+        player_state
+            .countdown()
+            .map(|seconds| format!("GET READY\n{}", seconds.ceil() as u32))
+    };
+    let value = value.filter(|_| *state.current() == AppState::Playing);
+
+    for (mut style, children) in banners.iter_mut() {
+        let display = if value.is_some() { Display::Flex } else { Display::None };
+        if style.display != display {
+            style.display = display;
+        }
+
+        for &child in children.iter() {
+            if let (Ok(mut text), Some(value)) = (texts.get_mut(child), &value) {
+                if text.sections[0].value != *value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+        }
+    }
+}
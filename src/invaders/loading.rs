@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    asset::LoadState,
+};
+
+use super::{
+    asset_path, AppState, EnemyKind, Hitboxes, SpriteInfos, BOSS_SPRITE, ENEMY_LASER_SPRITE,
+    EXPLOSION_SHEET, PLAYER_LASER_SPRITE, PLAYER_SPRITE,
+    hitbox::{HitboxFile, HitboxFileHandle},
+    waves::{WavesHandle, WAVES_FILE},
+};
+
+// the explosion sheet is a grid of frames, their size comes from the sheet's
+const EXPLOSION_COLUMNS: usize = 4;
+const EXPLOSION_ROWS: usize = 4;
+
+// Resource, every sprite the game needs, kept after loading to match reloads against
+struct SpriteHandles {
+    player: Handle<Image>,
+    player_laser: Handle<Image>,
+    enemies: HashMap<EnemyKind, Handle<Image>>,
+    enemy_laser: Handle<Image>,
+    boss: Handle<Image>,
+    explosion: Handle<Image>,
+}
+
+impl SpriteHandles {
+    fn load(asset_server: &AssetServer) -> Self {
+        Self {
+            player: asset_server.load(PLAYER_SPRITE),
+            player_laser: asset_server.load(PLAYER_LASER_SPRITE),
+            enemies: EnemyKind::ALL
+                .iter()
+                .map(|&kind| (kind, asset_server.load(kind.sprite())))
+                .collect(),
+            enemy_laser: asset_server.load(ENEMY_LASER_SPRITE),
+            boss: asset_server.load(BOSS_SPRITE),
+            explosion: asset_server.load(EXPLOSION_SHEET),
+        }
+    }
+
+    // the file of every handle, for telling which ones are missing
+    fn files(&self) -> Vec<(&'static str, &Handle<Image>)> {
+        let mut files = vec![
+            (PLAYER_SPRITE, &self.player),
+            (PLAYER_LASER_SPRITE, &self.player_laser),
+            (ENEMY_LASER_SPRITE, &self.enemy_laser),
+            (BOSS_SPRITE, &self.boss),
+            (EXPLOSION_SHEET, &self.explosion),
+        ];
+        files.extend(EnemyKind::ALL.iter().map(|kind| (kind.sprite(), &self.enemies[kind])));
+        files
+    }
+}
+
+// Resource, the full paths of the sprites that could not be loaded
+pub struct MissingAssets(pub Vec<String>);
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(start_loading)
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading)
+                    .with_system(finish_loading)
+            )
+            .add_system(reload_sprites);
+    }
+}
+
+fn start_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(SpriteHandles::load(&asset_server));
+}
+
+fn explosion_atlas(sheet: &Handle<Image>, sheet_size: Vec2) -> TextureAtlas {
+    let frame = sheet_size / Vec2::new(EXPLOSION_COLUMNS as f32, EXPLOSION_ROWS as f32);
+    TextureAtlas::from_grid(sheet.clone(), frame, EXPLOSION_COLUMNS, EXPLOSION_ROWS)
+}
+
+// the sizes are only known once the images are in, everything sized by them is made here
+fn finish_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    handles: Res<SpriteHandles>,
+    images: Res<Assets<Image>>,
+    hitbox_file: Res<HitboxFileHandle>,
+    hitbox_files: Res<Assets<HitboxFile>>,
+    waves_handle: Res<WavesHandle>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let files = handles.files();
+    let mut missing: Vec<String> = files
+        .iter()
+        .filter(|(_, handle)| asset_server.get_load_state(*handle) == LoadState::Failed)
+        .map(|(file, _)| asset_path(file).display().to_string())
+        .collect();
+    // without waves there is nothing to fight, a rejected file is as good as missing
+    let waves_state = asset_server.get_load_state(&waves_handle.0);
+    if waves_state == LoadState::Failed {
+        missing.push(asset_path(WAVES_FILE).display().to_string());
+    }
+    if !missing.is_empty() {
+        error!("Missing invaders assets: {}", missing.join(", "));
+        commands.insert_resource(MissingAssets(missing));
+        if let Err(e) = state.set(AppState::MissingAssets) {
+            warn!("Could not show the missing assets: {:?}", e);
+        }
+        return;
+    }
+    if files.iter().any(|(_, handle)| images.get(*handle).is_none()) || waves_state != LoadState::Loaded {
+        return;
+    }
+    // a broken or missing hitbox file only costs the sprite bounds, it doesn't stop the game
+    if matches!(asset_server.get_load_state(&hitbox_file.0), LoadState::NotLoaded | LoadState::Loading) {
+        return;
+    }
+
+    let sprite = |handle: &Handle<Image>| (handle.clone(), images.get(handle).unwrap().size());
+    let explosion_size = images.get(&handles.explosion).unwrap().size();
+    let sprite_infos = SpriteInfos {
+        player: sprite(&handles.player),
+        player_laser: sprite(&handles.player_laser),
+        enemies: handles.enemies.iter().map(|(&kind, handle)| (kind, sprite(handle))).collect(),
+        enemy_laser: sprite(&handles.enemy_laser),
+        boss: sprite(&handles.boss),
+        explosion: texture_atlases.add(explosion_atlas(&handles.explosion, explosion_size)),
+    };
+    commands.insert_resource(Hitboxes::new(&sprite_infos, hitbox_files.get(&hitbox_file.0)));
+    commands.insert_resource(sprite_infos);
+
+    if let Err(e) = state.set(AppState::Title) {
+        warn!("Could not leave the loading state: {:?}", e);
+    }
+}
+
+// sprites on screen pick up an edited file by themselves, the sizes and whatever comes
+// from them are redone here. Entities already spawned keep the hitbox they were given
+fn reload_sprites(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Image>>,
+    handles: Option<Res<SpriteHandles>>,
+    images: Res<Assets<Image>>,
+    sprite_infos: Option<ResMut<SpriteInfos>>,
+    hitbox_file: Res<HitboxFileHandle>,
+    hitbox_files: Res<Assets<HitboxFile>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let (handles, mut sprite_infos) = match (handles, sprite_infos) {
+        (Some(handles), Some(sprite_infos)) => (handles, sprite_infos),
+        _ => return,
+    };
+
+    let mut changed = false;
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Modified { handle } => handle,
+            _ => continue,
+        };
+        let size = match images.get(handle) {
+            Some(image) => image.size(),
+            None => continue,
+        };
+
+        if *handle == handles.explosion {
+            let atlas = explosion_atlas(&handles.explosion, size);
+            texture_atlases.set_untracked(&sprite_infos.explosion, atlas);
+            continue;
+        }
+        let sprite_infos = &mut *sprite_infos;
+        let sprites = [
+            &mut sprite_infos.player,
+            &mut sprite_infos.player_laser,
+            &mut sprite_infos.enemy_laser,
+            &mut sprite_infos.boss,
+        ];
+        for (sprite_handle, sprite_size) in sprites.into_iter().chain(sprite_infos.enemies.values_mut()) {
+            if sprite_handle == handle {
+                *sprite_size = size;
+                changed = true;
+            }
+        }
+    }
+
+    if changed {
+        info!("Invaders sprites reloaded");
+        commands.insert_resource(Hitboxes::new(&sprite_infos, hitbox_files.get(&hitbox_file.0)));
+    }
+}
@@ -1,70 +1,215 @@
 
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use bevy::math::Vec3Swizzles;
-use bevy::render::texture::ImageType;
 use bevy::{
     prelude::*,
-    sprite::collide_aabb::collide,
+    asset::{AssetServerSettings, FileAssetIo},
+};
+use rand::{
+    rngs::StdRng,
+    thread_rng,
+    Rng,
+    SeedableRng,
 };
 
 mod player;
 mod enemy;
-
-use enemy::EnemyPlugin;
+mod screens;
+mod hud;
+mod highscores;
+mod waves;
+mod boss;
+mod movement;
+mod simulation;
+mod hitbox;
+mod collision;
+mod powerups;
+mod bunkers;
+mod playfield;
+mod loading;
+
+use boss::BossPlugin;
+use bunkers::BunkerPlugin;
+use collision::CollisionPlugin;
+use enemy::{EnemyKind, EnemyPlugin, Formation};
+use highscores::HighScoresPlugin;
+use hitbox::{HitboxPlugin, Hitboxes};
+use hud::HudPlugin;
+use loading::LoadingPlugin;
 use player::PlayerPlugin;
+use playfield::PlayfieldPlugin;
+pub use playfield::WindowSettings;
+use powerups::PowerUpPlugin;
+use screens::ScreensPlugin;
+use simulation::SimulationPlugin;
+use waves::WavesPlugin;
+
+use crate::input_map::InputMapPlugin;
+use crate::widgets::WidgetPlugin;
 
-const SPRITE_DIR: &str = "assets";
+const ASSET_FOLDER: &str = "assets";
 const PLAYER_SPRITE: &str = "player_a_01.png";
 const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
 const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
-const ENEMY_SPRITE: &str = "enemy_a_01.png";
+const BOSS_SPRITE: &str = "boss_a_01.png";
 const EXPLOSION_SHEET: &str = "explo_a_sprite.png";
+const FONT: &str = "fonts/Symtext.ttf";
 
+// one step of the simulation stage
 const TIME_STEP: f32 = 1.0 / 60.0;
 const SCALE: f32 = 0.5;
-const MAX_ENEMIES: u32 = 4;
-const MAX_FORMATION_MEMBERS: u32 = 2;
-const PLAYER_RESPAWN_DELAY: f64 = 2.0;
+const PLAYER_RESPAWN_DELAY: f32 = 2.0;
+// the player can't be hit for this long after spawning
+const PLAYER_INVULNERABILITY: f32 = 2.0;
+// enemy lasers this close to the spawn point are cleared when the player comes back
+const SPAWN_CLEAR_RADIUS: f32 = 150.0;
+const PLAYER_LIVES: u32 = 3;
+// extra lives stop adding up here
+const MAX_LIVES: u32 = 5;
+const FORMATION_BONUS: u32 = 500;
+const WAVE_INTERMISSION: f32 = 2.0;
+const HIT_FLASH_DURATION: f32 = 0.1;
 
 // region:      Resources
+// every random choice in the game is drawn from here, so the same seed and inputs replay the same run
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl GameRng {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+// the systems drawing from GameRng, always one after the other in this order so a seed replays
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum RngDraw {
+    PowerUpDrops,
+    BunkerCraters,
+    EnemySpawn,
+    BossDeath,
+}
+
+// Paused is pushed on top of Playing, so leaving the pause screen doesn't restart the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AppState {
+    // until every sprite is in
+    Loading,
+    // some sprite could not be loaded, nothing else can happen
+    MissingAssets,
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+}
+
 struct SpriteInfos {
     player: (Handle<Image>, Vec2),
     player_laser: (Handle<Image>, Vec2),
-    enemy: (Handle<Image>, Vec2),
+    enemies: HashMap<EnemyKind, (Handle<Image>, Vec2)>,
     enemy_laser: (Handle<Image>, Vec2),
+    boss: (Handle<Image>, Vec2),
     explosion: Handle<TextureAtlas>,
 }
 
-struct WinSize {
+// the logical area the game happens in, centred on the origin whatever the window size
+struct Playfield {
     width: f32,
     height: f32,
 }
 
 struct ActiveEnemies(u32);
+
+#[derive(Default)]
+struct Score {
+    points: u32,
+    // enemies shot down per formation, to spot a formation being wiped out
+    formation_kills: HashMap<u32, u32>,
+}
+impl Score {
+    fn enemy_killed(&mut self, kind: EnemyKind, formation: &Formation) {
+        self.points += kind.points();
+
+        let group_id = formation.group_id;
+        let formation_kills = self.formation_kills.entry(group_id).or_insert(0);
+        *formation_kills += 1;
+        if *formation_kills == formation.size {
+            self.points += FORMATION_BONUS;
+            self.formation_kills.remove(&group_id);
+        }
+    }
+}
+
+struct Lives(u32);
+
+// the wave being fought, it only starts spawning once the "Wave N" banner is done
+struct Wave {
+    number: u32,
+    spawned: u32,
+    intermission: Timer,
+}
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            spawned: 0,
+            intermission: Timer::from_seconds(WAVE_INTERMISSION, false),
+        }
+    }
+}
+impl Wave {
+    fn next(&mut self) {
+        self.number += 1;
+        self.spawned = 0;
+        self.intermission.reset();
+    }
+}
+
 struct PlayerState {
     on: bool,
-    last_shot: f64,
+    // runs while the player is gone, the player comes back once it finishes
+    respawn: Timer,
 }
 impl Default for PlayerState {
     fn default() -> Self {
+        // the first player comes in straight away
+        let mut respawn = Timer::from_seconds(PLAYER_RESPAWN_DELAY, false);
+        respawn.tick(respawn.duration());
         Self {
             on: false,
-            last_shot: 0.0,
+            respawn,
         }
     }
 }
 impl PlayerState {
-    fn shot(&mut self, time: f64){
+    fn shot(&mut self) {
         self.on = false;
-        self.last_shot = time;
-        
+        self.respawn.reset();
     }
 
-    fn spawned(&mut self){
+    fn spawned(&mut self) {
         self.on = true;
-        self.last_shot = 0.0;
+    }
+
+    // advances the respawn timer, true when it is time for the player to come back
+    fn tick(&mut self, delta: Duration) -> bool {
+        !self.on && self.respawn.tick(delta).finished()
+    }
+
+    // seconds left before the player comes back
+    fn countdown(&self) -> Option<f32> {
+        if self.on || self.respawn.finished() {
+            None
+        } else {
+            Some(self.respawn.duration().as_secs_f32() - self.respawn.elapsed_secs())
+        }
     }
 }
 // endregion:   Resources
@@ -74,11 +219,21 @@ impl PlayerState {
 #[derive(Component)]
 struct Laser;
 
+// where a laser is headed
+#[derive(Component)]
+struct LaserDirection(Vec2);
+
 #[derive(Component)]
 struct Player;
 
+// enemy lasers go through the player until it runs out
 #[derive(Component)]
-struct PlayerReadyFire(bool);
+struct Invulnerable(Timer);
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Self(Timer::from_seconds(PLAYER_INVULNERABILITY, false))
+    }
+}
 
 #[derive(Component)]
 struct FromPlayer;
@@ -90,6 +245,19 @@ struct Enemy;
 #[derive(Component)]
 struct FromEnemy;
 
+// hits an enemy can still take
+#[derive(Component)]
+struct Health(u32);
+
+// tints a damaged enemy for a moment
+#[derive(Component)]
+struct HitFlash(Timer);
+impl Default for HitFlash {
+    fn default() -> Self {
+        Self(Timer::from_seconds(HIT_FLASH_DURATION, false))
+    }
+}
+
 #[derive(Component)]
 struct Explosion;
 
@@ -106,138 +274,44 @@ impl Default for Speed {
 // endregion:   Components
 
 
-fn setup(
+// clear whatever the previous game left behind
+fn reset_game(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut images: ResMut<Assets<Image>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    mut windows: ResMut<Windows>,
-) {
-    let window = windows.get_primary_mut().unwrap();
-
-    // camera
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-
-    // create the main resources
-    let texture_handle = asset_server.load(EXPLOSION_SHEET);
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(64.0, 64.0), 4, 4);
-
-    commands.insert_resource(SpriteInfos {
-        player: load_image(&mut images, PLAYER_SPRITE),
-        player_laser: load_image(&mut images, PLAYER_LASER_SPRITE),
-        enemy: load_image(&mut images, ENEMY_SPRITE),
-        enemy_laser: load_image(&mut images, ENEMY_LASER_SPRITE),
-        explosion: texture_atlases.add(texture_atlas)
-    });
-
-    commands.insert_resource(WinSize {
-        width: window.width(),
-        height: window.height(),
-    });
-
-    // position window
-    window.set_position(IVec2::new(2140,420));
-
-}
-
-fn load_image(images: &mut ResMut<Assets<Image>>, path: &str) -> (Handle<Image>, Vec2) {
-    let path = Path::new(SPRITE_DIR).join(path);
-    let bytes = std::fs::read(&path).expect(&format!("Cannot find {}", path.display()));
-    let image = Image::from_buffer(&bytes, ImageType::MimeType("image/png")).unwrap();
-    let size = image.texture_descriptor.size;
-    let size = Vec2::new(size.width as f32, size.height as f32);
-    let image_handle = images.add(image);
-    (image_handle, size)
-}
-
-fn player_laser_hit_enemy(
-    mut commands: Commands,
-    sprite_infos: Res<SpriteInfos>,
-    laser_query: Query<(Entity, &Transform), (With<Laser>, With<FromPlayer>)>,
-    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+    leftovers: Query<Entity, Or<(With<Player>, With<Enemy>, With<Laser>, With<Explosion>, With<ExplosionToSpawn>)>>,
     mut active_enemies: ResMut<ActiveEnemies>,
-) {
-    let mut enemies_blasted: HashSet<Entity> = HashSet::new();
-
-    for (laser_entity, laser_transform) in laser_query.iter() {
-        let player_laser_size = sprite_infos.player_laser.1;
-        let player_laser_scale = Vec2::from(laser_transform.scale.abs().xy());
-
-        for (enemy_entity, enemy_transform) in enemy_query.iter() {
-            let enemy_size = sprite_infos.enemy.1;
-            let enemy_scale = Vec2::from(enemy_transform.scale.xy());
-            let collision = collide(
-                laser_transform.translation,
-                player_laser_size * player_laser_scale,
-                enemy_transform.translation,
-                enemy_size * enemy_scale);
-
-            if let Some(_) = collision {
-                if enemies_blasted.get(&enemy_entity).is_none() {
-                    // remove the enemy
-                    commands.entity(enemy_entity).despawn();
-                    active_enemies.0 -= 1;
-
-                    // spawn explosion to spawn
-                    commands
-                        .spawn()
-                        .insert(ExplosionToSpawn(enemy_transform.translation.clone()));
-
-                    enemies_blasted.insert(enemy_entity);
-                }
-
-                // remove the laser
-                commands.entity(laser_entity).despawn();
-            }
-        }
-    }
-}
-
-fn enemy_laser_hit_player(
-    mut commands: Commands,
-    sprite_infos: Res<SpriteInfos>,
     mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
-    laser_query: Query<(Entity, &Transform), (With<Laser>, With<FromEnemy>)>,
-    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut wave: ResMut<Wave>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    if let Ok((player_entity, player_transform)) = player_query.get_single() {
-        let player_size = sprite_infos.player.1;
-        let player_scale = Vec2::from(player_transform.scale.xy());
-
-        for (laser_entity, laser_transform) in laser_query.iter() {
-            let laser_size = sprite_infos.enemy_laser.1;
-            let laser_scale = Vec2::from(laser_transform.scale.abs().xy());
-
-            let collision = collide(
-                laser_transform.translation,
-                laser_size * laser_scale,
-                player_transform.translation,
-                player_size * player_scale);
-
-            if let Some(_) = collision {
-                // remove the player
-                commands.entity(player_entity).despawn();
-                player_state.shot(time.seconds_since_startup());
-
-                // remove the laser
-                commands.entity(laser_entity).despawn();
-
-                // spawn explosion to spawn
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(player_transform.translation.clone()));
-            }
-        }
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn();
     }
+    active_enemies.0 = 0;
+    *player_state = PlayerState::default();
+    *score = Score::default();
+    lives.0 = PLAYER_LIVES;
+    *wave = Wave::default();
+    // every game starts from the seed again
+    *game_rng = GameRng::new(game_rng.seed);
 }
 
+// where the asset server finds a file, whatever directory the game was started from
+fn asset_path(path: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join(ASSET_FOLDER).join(path)
+}
 
 fn explosion_to_spawn(
     mut commands: Commands,
     query: Query<(Entity, &ExplosionToSpawn)>,
-    sprite_infos: Res<SpriteInfos>,
+    sprite_infos: Option<Res<SpriteInfos>>,
 ) {
+    // nothing explodes before the sprites are loaded
+    let sprite_infos = match sprite_infos {
+        Some(sprite_infos) => sprite_infos,
+        None => return,
+    };
     for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
         commands
             .spawn_bundle( SpriteSheetBundle {
@@ -277,22 +351,54 @@ fn animate_explosion(
     }
 }
 
-pub fn invaders_app() {
+pub fn invaders_app(seed: Option<u64>, window: WindowSettings) {
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    println!("Invaders seed: {} (replay it with --seed {})", seed, seed);
+    let window = WindowSettings::load(window);
+    let (width, height) = window.size();
+
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
         .insert_resource(WindowDescriptor {
             title: "Rust Invaders!".to_string(),
-            width: 598.0,
-            height: 676.0,
+            width,
+            height,
+            ..Default::default()
+        })
+        .insert_resource(window)
+        // hot reload the wave definitions
+        .insert_resource(AssetServerSettings {
+            asset_folder: ASSET_FOLDER.to_string(),
+            watch_for_changes: true,
             ..Default::default()
         })
         .insert_resource(ActiveEnemies(0))
+        .insert_resource(Score::default())
+        .insert_resource(Lives(PLAYER_LIVES))
+        .insert_resource(Wave::default())
+        .insert_resource(GameRng::new(seed))
         .add_plugins(DefaultPlugins)
+        .add_plugin(InputMapPlugin)
+        .add_plugin(WidgetPlugin)
+        .add_state(AppState::Loading)
+        .add_plugin(LoadingPlugin)
+        .add_plugin(PlayfieldPlugin)
+        .add_plugin(SimulationPlugin)
+        .add_plugin(HitboxPlugin)
+        .add_plugin(CollisionPlugin)
+        .add_plugin(ScreensPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(HighScoresPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(BunkerPlugin)
+        .add_plugin(WavesPlugin)
         .add_plugin(EnemyPlugin)
-        .add_startup_system(setup)
-        .add_system(player_laser_hit_enemy.system())
-        .add_system(enemy_laser_hit_player.system())
+        .add_plugin(BossPlugin)
+        .add_system_set(
+            SystemSet::on_enter(AppState::Playing)
+                .with_system(reset_game.system())
+        )
         .add_system(explosion_to_spawn.system())
         .add_system(animate_explosion.system())
         .run();
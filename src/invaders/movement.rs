@@ -0,0 +1,150 @@
+use std::f32::consts::PI;
+
+use bevy::math::Vec2;
+use serde::Deserialize;
+
+// horizontal gap between members of a row
+const MEMBER_SPACING: f32 = 60.0;
+// how far a row of DescendingRows drops at every turn
+const ROW_DROP: f32 = 40.0;
+// distance between members of a FollowTheLeader chain
+const TRAIL_DISTANCE: f32 = 55.0;
+// distance a BezierDive member waits in place between dives
+const DIVE_HOLD: f32 = 600.0;
+
+// region:      Movement patterns
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum MovementPattern {
+    // circle an ellipse around the formation offset
+    Orbit,
+    // march side to side as a row, one row lower at every turn
+    DescendingRows,
+    // sweep side to side as a row, bobbing up and down
+    SineSweep,
+    // wait in the row, then take turns diving at the bottom of the screen and loop back up
+    BezierDive,
+    // trace a figure eight around the formation offset, one behind the other
+    FigureEight,
+    // snake across the screen in a chain behind the first member
+    FollowTheLeader,
+}
+
+impl Default for MovementPattern {
+    fn default() -> Self {
+        MovementPattern::Orbit
+    }
+}
+
+// what a pattern needs to know about one formation member
+pub struct Path {
+    pub offset: Vec2,
+    pub radius: Vec2,
+    // position of the member within the formation, from 0
+    pub member: u32,
+    pub size: u32,
+    // 1.0 when the formation came in from the right, -1.0 from the left
+    pub dir: f32,
+    // half the playfield size
+    pub bounds: Vec2,
+}
+
+impl Path {
+    // offset of the member from the middle of its row
+    fn column(&self) -> f32 {
+        (self.member as f32 - (self.size as f32 - 1.0) / 2.0) * MEMBER_SPACING
+    }
+}
+
+impl MovementPattern {
+    // where a member is once it has travelled `distance` along the pattern
+    // (Orbit is angle based and stays with the formation itself)
+    pub fn position(&self, path: &Path, distance: f32) -> Vec2 {
+        match self {
+            MovementPattern::Orbit => path.offset,
+            MovementPattern::DescendingRows => descending_rows(path, distance),
+            MovementPattern::SineSweep => sine_sweep(path, distance),
+            MovementPattern::BezierDive => bezier_dive(path, distance),
+            MovementPattern::FigureEight => figure_eight(path, distance),
+            MovementPattern::FollowTheLeader => follow_the_leader(path, distance),
+        }
+    }
+}
+
+fn descending_rows(path: &Path, distance: f32) -> Vec2 {
+    // the row turns when its outer member reaches the side of the screen
+    let half_row = (path.size as f32 - 1.0) / 2.0 * MEMBER_SPACING;
+    let travel = (path.bounds.x - MEMBER_SPACING - half_row).max(MEMBER_SPACING);
+    let lane = 2.0 * travel;
+
+    let leg = (distance / lane).floor();
+    let along = distance - leg * lane;
+    let x = if leg as i32 % 2 == 0 { along - travel } else { travel - along };
+
+    // stop dropping a third of the way down, the player needs room to move
+    let floor = -path.bounds.y / 3.0;
+    let y = (path.offset.y - leg * ROW_DROP).max(floor);
+
+    Vec2::new(-path.dir * x + path.column(), y)
+}
+
+fn sine_sweep(path: &Path, distance: f32) -> Vec2 {
+    let travel = (path.bounds.x - MEMBER_SPACING).max(MEMBER_SPACING);
+    let phase = distance / travel;
+    Vec2::new(
+        path.dir * travel * phase.cos() + path.column(),
+        path.offset.y + path.radius.y * (3.0 * phase).sin(),
+    )
+}
+
+fn bezier_dive(path: &Path, distance: f32) -> Vec2 {
+    let home = Vec2::new(path.column(), path.offset.y);
+
+    // the dive dips close to the bottom of the screen and swings back up to its place in the row
+    let bottom = -path.bounds.y;
+    let swing = path.bounds.x * 0.8 * path.dir;
+    let p1 = Vec2::new(home.x - swing, bottom);
+    let p2 = Vec2::new(home.x + swing, bottom);
+    let dive_length = 2.5 * (home.y - bottom);
+
+    // members take turns, one dive after the other
+    let cycle = DIVE_HOLD + dive_length;
+    let turn = path.member as f32 * cycle / path.size.max(1) as f32;
+    let along = (distance + turn) % cycle;
+    if along < DIVE_HOLD {
+        return home;
+    }
+
+    cubic_bezier(home, p1, p2, home, (along - DIVE_HOLD) / dive_length)
+}
+
+fn figure_eight(path: &Path, distance: f32) -> Vec2 {
+    let scale = path.radius.x.min(path.radius.y);
+    let theta = path.dir * (distance - path.member as f32 * TRAIL_DISTANCE) / scale;
+    Vec2::new(
+        path.offset.x + path.radius.x * theta.sin(),
+        path.offset.y + path.radius.y * theta.sin() * theta.cos(),
+    )
+}
+
+fn follow_the_leader(path: &Path, distance: f32) -> Vec2 {
+    // every member goes where the leader was TRAIL_DISTANCE ago
+    let travel = (path.bounds.x - MEMBER_SPACING).max(MEMBER_SPACING);
+    let phase = (distance - path.member as f32 * TRAIL_DISTANCE) / travel;
+    let depth = path.bounds.y / 3.0;
+    Vec2::new(
+        path.dir * travel * phase.cos(),
+        path.offset.y - depth * (1.0 - (1.5 * phase).cos()) / 2.0,
+    )
+}
+
+fn cubic_bezier(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t = t.clamp(0.0, 1.0);
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+// angle the Orbit ellipse turns by over `distance`
+pub fn orbit_step(radius: Vec2, distance: f32) -> f32 {
+    distance / (radius.x.min(radius.y) * PI / 2.0)
+}
+// endregion:   Movement patterns
@@ -1,133 +1,528 @@
-use bevy::{
-    prelude::*,
-    core::FixedTimestep,
-};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::config::load_config;
+use crate::input_map::{update_action_state, Action, ActionState};
 
 use super::{
     Player,
-    PlayerReadyFire,
     Speed,
     TIME_STEP,
     SpriteInfos,
     Laser,
-    WinSize,
+    LaserDirection,
+    Playfield,
     FromPlayer,
+    FromEnemy,
+    Invulnerable,
     SCALE,
     PlayerState,
-    PLAYER_RESPAWN_DELAY,
+    SPAWN_CLEAR_RADIUS,
+    AppState,
+    simulation::{movement_systems, spawn_systems, Interpolated, SimulationStage},
+    hitbox::Hitboxes,
+    collision::Piercing,
 };
 
+// how often the player blinks while invulnerable
+const BLINK_INTERVAL: f32 = 0.1;
+// where the lasers leave the claws, from the middle of the sprite
+const CLAW_WIDTH: f32 = 144.0 / 4.0 - 5.0;
+const CLAW_HEIGHT: f32 = 19.0;
+// seconds fire is held for a charged shot, and how much bigger the shot is
+const CHARGE_TIME: f32 = 1.0;
+const CHARGED_SCALE: f32 = 2.5;
+const MAX_PLAYER_LASERS: usize = 8;
+const FIRE_FILE: &str = "invaders_fire.ron";
+const HANDLING_FILE: &str = "invaders_handling.ron";
+// stick travel ignored around the centre, so a worn stick doesn't drift the ship
+const STICK_DEADZONE: f32 = 0.2;
+// pixels per second per second
+const PLAYER_ACCELERATION: f32 = 3000.0;
+const PLAYER_FRICTION: f32 = 2500.0;
+
+// Resource, how the ship answers the controls, e.g. `(instant: true)`
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Handling {
+    // full speed at once and a dead stop on release, no acceleration or friction
+    pub instant: bool,
+    pub acceleration: f32,
+    pub friction: f32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self {
+            instant: false,
+            acceleration: PLAYER_ACCELERATION,
+            friction: PLAYER_FRICTION,
+        }
+    }
+}
+
+impl Handling {
+    fn load() -> Self {
+        let handling: Self = load_config(HANDLING_FILE);
+        // the velocity is clamped by these, they have to be above 0
+        let positive = |rate: f32| rate.is_finite() && rate > 0.0;
+        if positive(handling.acceleration) && positive(handling.friction) {
+            return handling;
+        }
+        warn!(
+            "Ignoring acceleration {} and friction {}, they must be above 0",
+            handling.acceleration, handling.friction,
+        );
+        Self {
+            instant: handling.instant,
+            ..Default::default()
+        }
+    }
+}
+
+// Component, the ship's sideways velocity
+#[derive(Component, Default)]
+pub struct Movement {
+    pub velocity: f32,
+}
+
+// Resource, how the fire button behaves, e.g. `(autofire: true, max_lasers: 12)`
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FireSettings {
+    // holding fire keeps shooting, otherwise every shot is a press and holding charges one up
+    pub autofire: bool,
+    // no shot that would put more than this many player lasers up
+    pub max_lasers: usize,
+}
+
+impl Default for FireSettings {
+    fn default() -> Self {
+        Self {
+            autofire: false,
+            max_lasers: MAX_PLAYER_LASERS,
+        }
+    }
+}
+
+// Resource, the fire button since the last simulation step
+#[derive(Default, Clone, Copy)]
+pub struct FireInput {
+    pub held: bool,
+    pub pressed: bool,
+    pub released: bool,
+    pub toggle_autofire: bool,
+}
+
+// Component, what the player fires, power-ups change it
+#[derive(Component)]
+pub struct Weapon {
+    // spread evenly between the claws
+    pub projectiles: u32,
+    // radians between the outermost lasers
+    pub spread: f32,
+    // seconds between shots
+    pub cooldown: f32,
+    pub sprite: Handle<Image>,
+    pub tint: Color,
+    // lasers go on through whatever they hit
+    pub piercing: bool,
+    reload: Timer,
+    // seconds fire has been held since the last press
+    charge: f32,
+}
+
+impl Weapon {
+    pub const BASE_PROJECTILES: u32 = 2;
+    pub const BASE_COOLDOWN: f32 = 0.25;
+
+    // the twin claw lasers
+    pub fn base(sprite: Handle<Image>) -> Self {
+        let mut reload = Timer::from_seconds(Self::BASE_COOLDOWN, false);
+        reload.tick(reload.duration());
+        Self {
+            projectiles: Self::BASE_PROJECTILES,
+            spread: 0.0,
+            cooldown: Self::BASE_COOLDOWN,
+            sprite,
+            tint: Color::WHITE,
+            piercing: false,
+            reload,
+            charge: 0.0,
+        }
+    }
+
+    // how far a charged shot is, from 0 to 1
+    pub fn charge(&self) -> f32 {
+        (self.charge / CHARGE_TIME).min(1.0)
+    }
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
         .insert_resource(PlayerState::default())
-        .add_startup_stage("game_setup_actors", SystemStage::single(player_spawn.system()))
-        .add_system(player_movement.system())
-        .add_system(player_fire.system())
-        .add_system(laser_movement.system())
+        .insert_resource(load_config::<FireSettings>(FIRE_FILE))
+        .init_resource::<FireInput>()
+        .insert_resource(Handling::load())
+        .add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::on_update(AppState::Playing)
+                .with_system(latch_fire_input.system().after(update_action_state))
+        )
         .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(0.5))
+            SystemSet::on_enter(AppState::Playing)
+                .with_system(reset_fire_input.system())
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            movement_systems()
                 .with_system(player_spawn.system())
+                .with_system(player_invulnerability.system())
+                .with_system(player_movement.system())
+                .with_system(laser_movement.system())
+        )
+        .add_system_set_to_stage(
+            SimulationStage,
+            spawn_systems()
+                .with_system(toggle_autofire.system())
+                .with_system(player_fire.system().after(toggle_autofire))
         );
     }
 }
 
+// counted in simulation steps, so the respawn takes just as long whatever the frame rate
 fn player_spawn(
     mut commands: Commands,
     textures: Res<SpriteInfos>,
-    win_size: Res<WinSize>,
-    time: Res<Time>,
-    mut player_state: ResMut<PlayerState>
+    hitboxes: Res<Hitboxes>,
+    playfield: Res<Playfield>,
+    mut player_state: ResMut<PlayerState>,
+    enemy_lasers: Query<(Entity, &Transform), (With<Laser>, With<FromEnemy>)>,
 ) {
-    let now = time.seconds_since_startup();
-    let last_shot = player_state. last_shot;
+    if !player_state.tick(Duration::from_secs_f32(TIME_STEP)) {
+        return;
+    }
+
+    let bottom = -playfield.height / 2.0;
+    let translation = Vec3::new(0.0, bottom + 75.0 / 4.0 + 5.0, 10.0);
+
+    // give the player a moment before the next laser arrives
+    for (laser_entity, laser_tf) in enemy_lasers.iter() {
+        if laser_tf.translation.truncate().distance(translation.truncate()) < SPAWN_CLEAR_RADIUS {
+            commands.entity(laser_entity).despawn();
+        }
+    }
 
     // spawn a sprite
-    if !player_state.on && (last_shot == 0.0 || now < last_shot + PLAYER_RESPAWN_DELAY) {
-        let bottom = -win_size.height / 2.0;
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: textures.player.0.clone(),
-                transform: Transform {
-                    translation: Vec3::new(0.0, bottom + 75.0 / 4.0 + 5.0, 10.0),
-                    scale: Vec3::new(SCALE, SCALE, 1.0),
-                    ..Default::default()
-                },
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: textures.player.0.clone(),
+            transform: Transform {
+                translation,
+                scale: Vec3::new(SCALE, SCALE, 1.0),
                 ..Default::default()
+            },
+            ..Default::default()
         })
         .insert(Player)
-        .insert(PlayerReadyFire(true))
-        .insert(Speed::default());
+        .insert(Speed::default())
+        .insert(Movement::default())
+        .insert(Invulnerable::default())
+        .insert(Weapon::base(textures.player_laser.0.clone()))
+        .insert(hitboxes.player.clone())
+        .insert(Interpolated::at(translation));
 
-        player_state.spawned();
+    player_state.spawned();
+}
+
+// blink while invulnerable, and stop being so when the time is up
+fn player_invulnerability(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable, &mut Visibility), With<Player>>,
+) {
+    for (entity, mut invulnerable, mut visibility) in query.iter_mut() {
+        if invulnerable.0.tick(Duration::from_secs_f32(TIME_STEP)).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+            visibility.is_visible = true;
+            continue;
+        }
+
+        visibility.is_visible = (invulnerable.0.elapsed_secs() / BLINK_INTERVAL) as u32 % 2 == 1;
     }
 }
 
+// the stick past its deadzone, rescaled to start from 0 again
+fn stick(value: f32) -> f32 {
+    if value.abs() < STICK_DEADZONE {
+        0.0
+    } else {
+        value.signum() * (value.abs() - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)
+    }
+}
+
+// the ship speeds up towards how far the stick is pushed, keys being all the way,
+// and slides to a stop on release. It stops dead at the edges of the playfield
 fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Speed, &mut Transform), With<Player>>
+    actions: Res<ActionState>,
+    handling: Res<Handling>,
+    playfield: Res<Playfield>,
+    textures: Res<SpriteInfos>,
+    mut query: Query<(&Speed, &mut Movement, &mut Transform), With<Player>>
 ) {
-    if let Ok((speed, mut transform)) = query.get_single_mut() {
-        let dir = if keyboard_input.pressed(KeyCode::Left) {
-            -1.0
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            1.0
+    if let Ok((speed, mut movement, mut transform)) = query.get_single_mut() {
+        let input = stick(actions.value(Action::MoveRight) - actions.value(Action::MoveLeft));
+        let target = input * speed.0;
+
+        movement.velocity = if handling.instant {
+            target
+        } else if input != 0.0 {
+            let change = handling.acceleration * TIME_STEP;
+            movement.velocity + (target - movement.velocity).clamp(-change, change)
         } else {
-            0.0
+            let change = handling.friction * TIME_STEP;
+            movement.velocity - movement.velocity.clamp(-change, change)
         };
-        transform.translation.x += dir * speed.0 * TIME_STEP;
+
+        let half_width = textures.player.1.x * SCALE / 2.0;
+        let limit = (playfield.width / 2.0 - half_width).max(0.0);
+        let x = transform.translation.x + movement.velocity * TIME_STEP;
+        if x.abs() > limit {
+            movement.velocity = 0.0;
+        }
+        transform.translation.x = x.clamp(-limit, limit);
     }
 }
 
-fn player_fire(
+// the fire input is read every frame but used by the simulation, which may step any number of
+// times a frame, so presses and releases are held on to until a step has seen them
+fn latch_fire_input(
+    actions: Res<ActionState>,
+    mut input: ResMut<FireInput>,
+) {
+    input.held = actions.pressed(Action::Fire);
+    input.pressed |= actions.just_pressed(Action::Fire);
+    input.released |= actions.just_released(Action::Fire);
+    input.toggle_autofire |= actions.just_pressed(Action::ToggleAutofire);
+}
+
+fn reset_fire_input(mut input: ResMut<FireInput>) {
+    *input = FireInput::default();
+}
+
+pub fn toggle_autofire(
+    mut input: ResMut<FireInput>,
+    mut settings: ResMut<FireSettings>,
+) {
+    if input.toggle_autofire {
+        settings.autofire = !settings.autofire;
+        input.toggle_autofire = false;
+    }
+}
+
+// shots come as fast as the weapon cooldown allows, a press each or held down with autofire.
+// Without autofire, holding fire past CHARGE_TIME lets a charged shot go on release
+pub fn player_fire(
     mut commands: Commands,
-    keyboard_input: Res<Input<KeyCode>>,
-    textures: Res<SpriteInfos>,
-    mut query: Query<(&Transform, &mut PlayerReadyFire), With<Player>>
+    mut input: ResMut<FireInput>,
+    settings: Res<FireSettings>,
+    hitboxes: Res<Hitboxes>,
+    mut query: Query<(&Transform, &mut Weapon), With<Player>>,
+    lasers: Query<(), (With<Laser>, With<FromPlayer>)>,
 ) {
-    if let Ok((player_tf, mut ready_fire)) = query.get_single_mut() {
-        if ready_fire.0 && keyboard_input.pressed(KeyCode::Space) {
-            let x = player_tf.translation.x;
-            let y = player_tf.translation.y + 19.0; // manually defined "claw height" offset
-            let mut spawn_laser = |x_offset: f32| {
-                commands.spawn_bundle(SpriteBundle {
-                    texture: textures.player_laser.0.clone(),
-                    transform: Transform {
-                        translation: Vec3::new(x + x_offset, y, 0.0),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Laser)
-                .insert(FromPlayer)
-                .insert(Speed::default());
-                ready_fire.0 = false;
-            };
-            let x_offset = 144.0 / 4.0 - 5.0; // manually defined "claw width"
-            spawn_laser(x_offset);
-            spawn_laser(-x_offset);
+    // whatever happened to the button is used up by this step, with or without a player
+    let FireInput { held, pressed, released, .. } = *input;
+    input.pressed = false;
+    input.released = false;
+
+    if let Ok((player_tf, mut weapon)) = query.get_single_mut() {
+        weapon.reload.tick(Duration::from_secs_f32(TIME_STEP));
+        // a whole volley has to fit under the cap, a cap smaller than one volley still lets it go
+        // when no laser is up
+        let count = lasers.iter().count();
+        let volley = weapon.projectiles.max(1) as usize;
+        let room = count + volley <= settings.max_lasers.max(volley);
+        let room_charged = count < settings.max_lasers.max(1);
+        let from = player_tf.translation;
+
+        if settings.autofire {
+            weapon.charge = 0.0;
+            if (held || pressed) && weapon.reload.finished() && room {
+                fire(&mut commands, &mut weapon, &hitboxes, from);
+            }
+            return;
+        }
+
+        if pressed {
+            weapon.charge = 0.0;
+            if weapon.reload.finished() && room {
+                fire(&mut commands, &mut weapon, &hitboxes, from);
+            }
+        } else if held {
+            weapon.charge += TIME_STEP;
         }
-            
 
-        if keyboard_input.just_released(KeyCode::Space) {
-            ready_fire.0 = true;
+        if released {
+            if weapon.charge() >= 1.0 && room_charged {
+                fire_charged(&mut commands, &mut weapon, &hitboxes, from);
+            }
+            weapon.charge = 0.0;
         }
     }
 }
 
+// a volley spread evenly between the claws
+fn fire(commands: &mut Commands, weapon: &mut Weapon, hitboxes: &Hitboxes, from: Vec3) {
+    let count = weapon.projectiles.max(1);
+    for i in 0..count {
+        // -0.5 for the leftmost laser to 0.5 for the rightmost
+        let t = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+        let angle = t * weapon.spread;
+        let translation = Vec3::new(from.x + 2.0 * t * CLAW_WIDTH, from.y + CLAW_HEIGHT, 0.0);
+        spawn_laser(commands, weapon, hitboxes, translation, angle, 1.0, weapon.piercing);
+    }
+    reload(weapon);
+}
+
+// one big laser from the middle that goes through everything
+fn fire_charged(commands: &mut Commands, weapon: &mut Weapon, hitboxes: &Hitboxes, from: Vec3) {
+    let translation = Vec3::new(from.x, from.y + CLAW_HEIGHT, 0.0);
+    spawn_laser(commands, weapon, hitboxes, translation, 0.0, CHARGED_SCALE, true);
+    reload(weapon);
+}
+
+fn reload(weapon: &mut Weapon) {
+    let cooldown = weapon.cooldown;
+    weapon.reload.set_duration(Duration::from_secs_f32(cooldown));
+    weapon.reload.reset();
+}
+
+fn spawn_laser(
+    commands: &mut Commands,
+    weapon: &Weapon,
+    hitboxes: &Hitboxes,
+    translation: Vec3,
+    angle: f32,
+    scale: f32,
+    piercing: bool,
+) {
+    let mut laser = commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: weapon.tint,
+            ..Default::default()
+        },
+        texture: weapon.sprite.clone(),
+        transform: Transform {
+            translation,
+            // the sprite points up, turn it towards where it's headed
+            rotation: Quat::from_rotation_z(-angle),
+            scale: Vec3::new(scale, scale, 1.0),
+        },
+        ..Default::default()
+    });
+    laser
+        .insert(Laser)
+        .insert(FromPlayer)
+        .insert(LaserDirection(Vec2::new(angle.sin(), angle.cos())))
+        .insert(Speed::default())
+        .insert(hitboxes.player_laser.clone())
+        .insert(Interpolated::at(translation));
+    if piercing {
+        laser.insert(Piercing::default());
+    }
+}
+
 fn laser_movement(
     mut commands: Commands,
-    win_size: Res<WinSize>,
-    mut query: Query<(Entity, &Speed, &mut Transform), (With<Laser>, With<FromPlayer>)>
+    playfield: Res<Playfield>,
+    mut query: Query<(Entity, &Speed, &LaserDirection, &mut Transform), (With<Laser>, With<FromPlayer>)>
 ) {
-    for (laser_entity, speed, mut laser_tf) in query.iter_mut() {
+    for (laser_entity, speed, direction, mut laser_tf) in query.iter_mut() {
         let translation = &mut laser_tf.translation;
-        translation.y += speed.0 * TIME_STEP;
-        if translation.y > win_size.height {
+        translation.x += direction.0.x * speed.0 * TIME_STEP;
+        translation.y += direction.0.y * speed.0 * TIME_STEP;
+        if translation.y > playfield.height / 2.0 + 50.0
+            || translation.x.abs() > playfield.width / 2.0 + 50.0
+        {
             commands.entity(laser_entity).despawn();
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invaders::{PLAYER_INVULNERABILITY, PLAYER_RESPAWN_DELAY};
+
+    fn step() -> Duration {
+        Duration::from_secs_f32(TIME_STEP)
+    }
+
+    #[test]
+    fn respawns_once_the_delay_is_over() {
+        let mut player_state = PlayerState::default();
+        player_state.spawned();
+        player_state.shot();
+
+        let mut steps = 1;
+        while !player_state.tick(step()) {
+            assert!(
+                steps as f32 * TIME_STEP < PLAYER_RESPAWN_DELAY + TIME_STEP,
+                "still no respawn after {} steps",
+                steps,
+            );
+            steps += 1;
+        }
+        // not a step early, and on the step the delay is crossed
+        assert!(steps as f32 * TIME_STEP >= PLAYER_RESPAWN_DELAY - 1e-4);
+        assert!(((steps - 1) as f32 * TIME_STEP) < PLAYER_RESPAWN_DELAY);
+    }
+
+    #[test]
+    fn countdown_runs_out() {
+        let mut player_state = PlayerState::default();
+        player_state.spawned();
+        assert_eq!(player_state.countdown(), None);
+        player_state.shot();
+
+        let mut last = player_state.countdown().unwrap();
+        assert!((last - PLAYER_RESPAWN_DELAY).abs() < 1e-4);
+        while !player_state.tick(step()) {
+            let countdown = player_state.countdown().unwrap();
+            assert!(countdown < last);
+            last = countdown;
+        }
+        assert_eq!(player_state.countdown(), None);
+    }
+
+    #[test]
+    fn invulnerability_wears_off() {
+        let mut app = App::new();
+        app.add_system(player_invulnerability);
+        let player = app
+            .world
+            .spawn()
+            .insert(Player)
+            .insert(Invulnerable::default())
+            .insert(Visibility::default())
+            .id();
+
+        let mut steps = 0;
+        while app.world.get::<Invulnerable>(player).is_some() {
+            assert!(
+                (steps as f32 * TIME_STEP) < PLAYER_INVULNERABILITY + TIME_STEP,
+                "still invulnerable after {} steps",
+                steps,
+            );
+            app.update();
+            steps += 1;
+        }
+        assert!(steps as f32 * TIME_STEP >= PLAYER_INVULNERABILITY - 1e-4);
+        // and it doesn't stay blinked out
+        assert!(app.world.get::<Visibility>(player).unwrap().is_visible);
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::{
+    prelude::*,
+    window::{WindowId, WindowResized},
+};
+use serde::Deserialize;
+
+use crate::config::load_config;
+
+use super::Playfield;
+
+const WINDOW_FILE: &str = "invaders_window.ron";
+// the logical size the whole game is laid out in, it is scaled to fit the window
+pub const PLAYFIELD_WIDTH: f32 = 598.0;
+pub const PLAYFIELD_HEIGHT: f32 = 676.0;
+const LETTERBOX_COLOR: Color = Color::BLACK;
+// above the game, the ui is drawn on top of it anyway
+const LETTERBOX_Z: f32 = 900.0;
+// wider than any window could show
+const LETTERBOX_SIZE: f32 = 100_000.0;
+
+// region:      Window settings
+// Resource, how the window opens, e.g. `(size: Some((800.0, 900.0)), position: Some((100, 50)))`
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub size: Option<(f32, f32)>,
+    pub position: Option<(i32, i32)>,
+}
+
+impl WindowSettings {
+    // the config file, with whatever came on the command line replacing it
+    pub fn load(overrides: WindowSettings) -> Self {
+        let file = Self::read();
+        Self {
+            size: overrides.size.or(file.size),
+            position: overrides.position.or(file.position),
+        }
+    }
+
+    // the window size, the playfield's own when nothing says otherwise
+    pub fn size(&self) -> (f32, f32) {
+        self.size.unwrap_or((PLAYFIELD_WIDTH, PLAYFIELD_HEIGHT))
+    }
+
+    // the playfield can only be fitted into a window with some room in it
+    pub fn valid_size((width, height): (f32, f32)) -> bool {
+        width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0
+    }
+
+    fn read() -> Self {
+        let mut settings: Self = load_config(WINDOW_FILE);
+        if let Some(size) = settings.size.filter(|&size| !Self::valid_size(size)) {
+            warn!("Ignoring the window size {:?} in {}, it must be above 0", size, WINDOW_FILE);
+            settings.size = None;
+        }
+        settings
+    }
+}
+// endregion:   Window settings
+
+#[derive(Component)]
+struct PlayfieldCamera;
+
+pub struct PlayfieldPlugin;
+
+impl Plugin for PlayfieldPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Playfield {
+                width: PLAYFIELD_WIDTH,
+                height: PLAYFIELD_HEIGHT,
+            })
+            .add_startup_system(setup)
+            .add_system(track_window);
+    }
+}
+
+// how many world units a window pixel shows for the playfield to just fit
+fn fit_scale(playfield: &Playfield, width: f32, height: f32) -> f32 {
+    (playfield.width / width).max(playfield.height / height)
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<WindowSettings>,
+    playfield: Res<Playfield>,
+    mut windows: ResMut<Windows>,
+) {
+    let window = windows.get_primary_mut().unwrap();
+    if let Some((x, y)) = settings.position {
+        window.set_position(IVec2::new(x, y));
+    }
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.orthographic_projection.scale = fit_scale(&playfield, window.width(), window.height());
+    commands.spawn_bundle(camera).insert(PlayfieldCamera);
+
+    // bars all around the playfield hide whatever is outside it when the window is another shape
+    let (half_width, half_height) = (playfield.width / 2.0, playfield.height / 2.0);
+    let reach = LETTERBOX_SIZE / 2.0;
+    let bars = [
+        Vec2::new(-half_width - reach, 0.0),
+        Vec2::new(half_width + reach, 0.0),
+        Vec2::new(0.0, half_height + reach),
+        Vec2::new(0.0, -half_height - reach),
+    ];
+    for bar in bars {
+        commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: LETTERBOX_COLOR,
+                custom_size: Some(Vec2::splat(LETTERBOX_SIZE)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(bar.extend(LETTERBOX_Z)),
+            ..Default::default()
+        });
+    }
+}
+
+fn track_window(
+    mut resized: EventReader<WindowResized>,
+    playfield: Res<Playfield>,
+    mut cameras: Query<&mut OrthographicProjection, With<PlayfieldCamera>>,
+) {
+    for event in resized.iter() {
+        // minimised windows report no size at all
+        if event.id != WindowId::primary() || event.width <= 0.0 || event.height <= 0.0 {
+            continue;
+        }
+
+        for mut projection in cameras.iter_mut() {
+            projection.scale = fit_scale(&playfield, event.width, event.height);
+        }
+    }
+}
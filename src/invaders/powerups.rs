@@ -0,0 +1,341 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    hitbox::{Hitbox, Shape},
+    player::Weapon,
+    simulation::{collision_systems, movement_systems, Interpolated, SimulationStage},
+    AppState, GameRng, Lives, Player, Playfield, RngDraw, FONT, MAX_LIVES, TIME_STEP,
+};
+
+// the chance a kill drops something
+const DROP_CHANCE: f64 = 0.1;
+const TOKEN_SIZE: f32 = 24.0;
+const TOKEN_SPEED: f32 = 120.0;
+const TOKEN_FONT_SIZE: f32 = 18.0;
+// what each rapid fire stack leaves of the weapon cooldown
+const RAPID_COOLDOWN_FACTOR: f32 = 0.6;
+// radians between the outermost lasers, per spread stack
+const SPREAD_ANGLE: f32 = 0.25;
+const SHIELD_TINT: Color = Color::rgb(0.5, 0.8, 1.0);
+
+// region:      Kinds
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerUpKind {
+    Spread,
+    Rapid,
+    Piercing,
+    Shield,
+    ExtraLife,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::Spread,
+        PowerUpKind::Rapid,
+        PowerUpKind::Piercing,
+        PowerUpKind::Shield,
+        PowerUpKind::ExtraLife,
+    ];
+
+    // seconds it lasts, collecting it again starts it over, None for the ones used up at once
+    fn duration(&self) -> Option<f32> {
+        match self {
+            PowerUpKind::Spread => Some(10.0),
+            PowerUpKind::Rapid => Some(8.0),
+            PowerUpKind::Piercing => Some(6.0),
+            PowerUpKind::Shield => Some(15.0),
+            PowerUpKind::ExtraLife => None,
+        }
+    }
+
+    // how many times it adds up, a shield stack takes one hit
+    fn max_stacks(&self) -> u32 {
+        match self {
+            PowerUpKind::Spread => 3,
+            PowerUpKind::Rapid => 3,
+            PowerUpKind::Piercing => 1,
+            PowerUpKind::Shield => 3,
+            PowerUpKind::ExtraLife => 1,
+        }
+    }
+
+    // how often it drops compared to the others
+    fn weight(&self) -> u32 {
+        match self {
+            PowerUpKind::Spread => 4,
+            PowerUpKind::Rapid => 4,
+            PowerUpKind::Piercing => 2,
+            PowerUpKind::Shield => 3,
+            PowerUpKind::ExtraLife => 1,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            PowerUpKind::Spread => Color::rgb(1.0, 0.6, 0.1),
+            PowerUpKind::Rapid => Color::rgb(1.0, 0.9, 0.2),
+            PowerUpKind::Piercing => Color::rgb(0.8, 0.3, 1.0),
+            PowerUpKind::Shield => Color::rgb(0.2, 0.6, 1.0),
+            PowerUpKind::ExtraLife => Color::rgb(0.2, 0.9, 0.3),
+        }
+    }
+
+    // on the falling token and in the hud
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUpKind::Spread => "SPREAD",
+            PowerUpKind::Rapid => "RAPID",
+            PowerUpKind::Piercing => "PIERCE",
+            PowerUpKind::Shield => "SHIELD",
+            PowerUpKind::ExtraLife => "1UP",
+        }
+    }
+}
+// endregion:   Kinds
+
+// region:      Resources
+struct Active {
+    stacks: u32,
+    timer: Timer,
+}
+
+// Resource, the power-ups the player has
+#[derive(Default)]
+pub struct PowerUps {
+    active: HashMap<PowerUpKind, Active>,
+}
+
+impl PowerUps {
+    // another stack up to the kind's limit, and the full duration again
+    pub fn collect(&mut self, kind: PowerUpKind) {
+        let duration = match kind.duration() {
+            Some(duration) => duration,
+            None => return,
+        };
+        let active = self.active.entry(kind).or_insert_with(|| Active {
+            stacks: 0,
+            timer: Timer::from_seconds(duration, false),
+        });
+        active.stacks = (active.stacks + 1).min(kind.max_stacks());
+        active.timer.reset();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    pub fn stacks(&self, kind: PowerUpKind) -> u32 {
+        self.active.get(&kind).map_or(0, |active| active.stacks)
+    }
+
+    // what is active, with the seconds it has left, in a steady order
+    pub fn remaining(&self) -> Vec<(PowerUpKind, u32, f32)> {
+        PowerUpKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                self.active.get(kind).map(|active| {
+                    let left = active.timer.duration().as_secs_f32() - active.timer.elapsed_secs();
+                    (*kind, active.stacks, left)
+                })
+            })
+            .collect()
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        for active in self.active.values_mut() {
+            active.timer.tick(delta);
+        }
+        self.active.retain(|_, active| !active.timer.finished() && active.stacks > 0);
+    }
+
+    // a shield stack takes the hit, false when there is no shield left
+    pub fn absorb_hit(&mut self) -> bool {
+        match self.active.get_mut(&PowerUpKind::Shield) {
+            Some(shield) if shield.stacks > 0 => {
+                shield.stacks -= 1;
+                if shield.stacks == 0 {
+                    self.active.remove(&PowerUpKind::Shield);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+}
+// endregion:   Resources
+
+// region:      Components
+// where a shot down enemy might drop something, rolled for in the simulation
+#[derive(Component)]
+pub struct PowerUpDrop(pub Vec3);
+
+#[derive(Component)]
+struct PowerUpToken;
+// endregion:   Components
+
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PowerUps>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(reset_power_ups)
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                movement_systems()
+                    .with_system(drop_power_ups.label(RngDraw::PowerUpDrops))
+                    .with_system(token_movement)
+                    .with_system(tick_power_ups)
+                    .with_system(upgrade_weapon.after(tick_power_ups))
+            )
+            .add_system_set_to_stage(
+                SimulationStage,
+                collision_systems()
+                    .with_system(collect_power_ups)
+            );
+    }
+}
+
+fn reset_power_ups(
+    mut commands: Commands,
+    mut power_ups: ResMut<PowerUps>,
+    leftovers: Query<Entity, Or<(With<PowerUpToken>, With<PowerUpDrop>)>>,
+) {
+    power_ups.clear();
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn drop_power_ups(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut game_rng: ResMut<GameRng>,
+    drops: Query<(Entity, &PowerUpDrop)>,
+) {
+    for (entity, drop) in drops.iter() {
+        commands.entity(entity).despawn();
+
+        let rng = &mut game_rng.rng;
+        if !rng.gen_bool(DROP_CHANCE) {
+            continue;
+        }
+        let kind = match PowerUpKind::ALL.choose_weighted(rng, PowerUpKind::weight) {
+            Ok(&kind) => kind,
+            Err(_) => continue,
+        };
+
+        let translation = Vec3::new(drop.0.x, drop.0.y, 5.0);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: kind.color(),
+                    custom_size: Some(Vec2::splat(TOKEN_SIZE)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            })
+            .insert(PowerUpToken)
+            .insert(kind)
+            .insert(Hitbox(vec![Shape::Circle { center: (0.0, 0.0), radius: TOKEN_SIZE / 2.0 }]))
+            .insert(Interpolated::at(translation))
+            .with_children(|parent| {
+                parent.spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        &kind.name()[..1],
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: TOKEN_FONT_SIZE,
+                            color: Color::BLACK,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..Default::default()
+                });
+            });
+    }
+}
+
+// tokens drift down and are gone at the bottom of the screen
+fn token_movement(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut query: Query<(Entity, &mut Transform), With<PowerUpToken>>,
+) {
+    for (entity, mut tf) in query.iter_mut() {
+        tf.translation.y -= TOKEN_SPEED * TIME_STEP;
+        if tf.translation.y < -playfield.height / 2.0 - TOKEN_SIZE {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn collect_power_ups(
+    mut commands: Commands,
+    mut power_ups: ResMut<PowerUps>,
+    mut lives: ResMut<Lives>,
+    player_query: Query<(&Transform, &Hitbox), With<Player>>,
+    tokens: Query<(Entity, &Transform, &Hitbox, &PowerUpKind), With<PowerUpToken>>,
+) {
+    let (player_tf, player_hitbox) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for (entity, tf, hitbox, kind) in tokens.iter() {
+        if !hitbox.intersects(tf, player_hitbox, player_tf) {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        match kind {
+            PowerUpKind::ExtraLife => lives.0 = (lives.0 + 1).min(MAX_LIVES),
+            _ => power_ups.collect(*kind),
+        }
+    }
+}
+
+fn tick_power_ups(mut power_ups: ResMut<PowerUps>) {
+    // ticking marks the resource changed, and the hud redraws whenever it is
+    if power_ups.is_empty() {
+        return;
+    }
+    power_ups.tick(Duration::from_secs_f32(TIME_STEP));
+}
+
+// the weapon is rebuilt from the base one and whatever is active
+fn upgrade_weapon(
+    power_ups: Res<PowerUps>,
+    mut query: Query<(&mut Weapon, &mut Sprite), With<Player>>,
+) {
+    for (mut weapon, mut sprite) in query.iter_mut() {
+        let spread = power_ups.stacks(PowerUpKind::Spread);
+        let rapid = power_ups.stacks(PowerUpKind::Rapid);
+        let piercing = power_ups.stacks(PowerUpKind::Piercing) > 0;
+
+        weapon.projectiles = Weapon::BASE_PROJECTILES + spread;
+        weapon.spread = SPREAD_ANGLE * spread as f32;
+        weapon.cooldown = Weapon::BASE_COOLDOWN * RAPID_COOLDOWN_FACTOR.powi(rapid as i32);
+        weapon.piercing = piercing;
+        weapon.tint = if piercing { PowerUpKind::Piercing.color() } else { Color::WHITE };
+
+        let color = if power_ups.stacks(PowerUpKind::Shield) > 0 { SHIELD_TINT } else { Color::WHITE };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use bevy::{
+    prelude::*,
+    app::AppExit,
+};
+
+use crate::input_map::{Action, ActionState};
+use crate::widgets::{
+    spawn_button, spawn_label, spawn_text_input, Autofocus, TextInput, WidgetAction, WidgetEvent,
+    WidgetStyle,
+};
+
+use super::highscores::{HighScores, SUBMIT_INITIALS};
+use super::{loading::MissingAssets, AppState, Score, FONT};
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const BUTTON_FONT_SIZE: f32 = 24.0;
+const TEXT_FONT_SIZE: f32 = 20.0;
+
+const START: WidgetAction = WidgetAction("invaders.start");
+const RESUME: WidgetAction = WidgetAction("invaders.resume");
+const RESTART: WidgetAction = WidgetAction("invaders.restart");
+const TO_TITLE: WidgetAction = WidgetAction("invaders.to_title");
+const QUIT: WidgetAction = WidgetAction("invaders.quit");
+
+// root node of whatever screen is showing, despawned when leaving it
+#[derive(Component)]
+struct Screen;
+
+pub struct ScreensPlugin;
+
+impl Plugin for ScreensPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(setup)
+            .add_system_set(SystemSet::on_enter(AppState::MissingAssets).with_system(missing_assets_screen))
+            .add_system_set(SystemSet::on_enter(AppState::Title).with_system(title_screen))
+            .add_system_set(SystemSet::on_exit(AppState::Title).with_system(despawn_screen))
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(pause_screen))
+            .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(despawn_screen))
+            .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(game_over_screen))
+            .add_system_set(SystemSet::on_exit(AppState::GameOver).with_system(despawn_screen))
+            .add_system(screen_actions)
+            .add_system(pause_key);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut widget_style: ResMut<WidgetStyle>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    widget_style.font = asset_server.load(FONT);
+}
+
+fn screen_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..Default::default()
+    }
+}
+
+fn screen_button_style() -> Style {
+    Style {
+        size: Size::new(Val::Px(220.0), Val::Px(50.0)),
+        margin: Rect::all(Val::Px(8.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    }
+}
+
+// title, then whatever the screen adds, then its buttons
+fn spawn_screen(
+    commands: &mut Commands,
+    title: &str,
+    content: impl FnOnce(&mut ChildBuilder),
+    buttons: &[(WidgetAction, &str)],
+) {
+    commands
+        .spawn_bundle(screen_root())
+        .insert(Screen)
+        .with_children(|parent| {
+            spawn_label(parent, title, TITLE_FONT_SIZE);
+            content(parent);
+            for &(action, label) in buttons {
+                spawn_button(parent, action, label, screen_button_style(), BUTTON_FONT_SIZE);
+            }
+        });
+}
+
+fn title_screen(
+    mut commands: Commands,
+    high_scores: Res<HighScores>,
+) {
+    let high_score_table = |parent: &mut ChildBuilder| {
+        if high_scores.0.is_empty() {
+            return;
+        }
+
+        spawn_label(parent, "HIGH SCORES", TEXT_FONT_SIZE);
+        for (rank, entry) in high_scores.0.iter().enumerate() {
+            let line = format!("{:>2}. {:<3} {:06}", rank + 1, entry.initials, entry.points);
+            spawn_label(parent, &line, TEXT_FONT_SIZE);
+        }
+    };
+    spawn_screen(&mut commands, "RUST INVADERS", high_score_table, &[(START, "PLAY"), (QUIT, "QUIT")]);
+}
+
+fn missing_assets_screen(
+    mut commands: Commands,
+    missing: Res<MissingAssets>,
+) {
+    let missing_files = |parent: &mut ChildBuilder| {
+        spawn_label(parent, "COULD NOT LOAD", TEXT_FONT_SIZE);
+        for path in missing.0.iter() {
+            spawn_label(parent, path, TEXT_FONT_SIZE);
+        }
+    };
+    spawn_screen(&mut commands, "MISSING ASSETS", missing_files, &[(QUIT, "QUIT")]);
+}
+
+fn pause_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, "PAUSED", |_| {}, &[(RESUME, "RESUME"), (TO_TITLE, "TITLE")]);
+}
+
+fn game_over_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    high_scores: Res<HighScores>,
+) {
+    let final_score = |parent: &mut ChildBuilder| {
+        spawn_label(parent, &format!("SCORE {:06}", score.points), TEXT_FONT_SIZE);
+        if high_scores.qualifies(score.points) {
+            spawn_label(parent, "NEW HIGH SCORE - ENTER INITIALS", TEXT_FONT_SIZE);
+            let initials = TextInput {
+                label: "INITIALS".to_string(),
+                value: String::new(),
+            };
+            spawn_text_input(parent, SUBMIT_INITIALS, initials, screen_button_style(), BUTTON_FONT_SIZE)
+                .insert(Autofocus);
+        }
+    };
+    spawn_screen(&mut commands, "GAME OVER", final_score, &[(RESTART, "RESTART"), (TO_TITLE, "TITLE")]);
+}
+
+fn despawn_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<Screen>>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn screen_actions(
+    mut widget_events: EventReader<WidgetEvent>,
+    mut state: ResMut<State<AppState>>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    for event in widget_events.iter() {
+        let result = match event.action {
+            START | RESTART => state.set(AppState::Playing),
+            // Paused sits on top of Playing, so resuming pops back without restarting
+            RESUME => state.pop(),
+            TO_TITLE => state.replace(AppState::Title),
+            QUIT => {
+                exit_writer.send(AppExit);
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("Ignoring screen change: {:?}", e);
+        }
+    }
+}
+
+fn pause_key(
+    actions: Res<ActionState>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+
+    let result = match state.current() {
+        AppState::Playing => state.push(AppState::Paused),
+        AppState::Paused => state.pop(),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        warn!("Ignoring pause: {:?}", e);
+    }
+}
@@ -0,0 +1,312 @@
+use bevy::{
+    prelude::*,
+    ecs::schedule::ShouldRun,
+    transform::TransformSystem,
+};
+
+use super::{AppState, TIME_STEP};
+
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+// Stage, the gameplay motion and collisions, run every TIME_STEP of game time however fast frames come.
+// The systems in it only ever advance by TIME_STEP, never by the frame time, so running them in a
+// stage of their own steps the game by hand
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct SimulationStage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum SimulationSystem {
+    // puts every Interpolated entity back where the simulation left it
+    Restore,
+    // moves things by one TIME_STEP
+    Movement,
+    // finds what ended up touching
+    Collision,
+    // acts on what the collision systems found
+    Response,
+    // spawns and fires, from where the step left everything
+    Spawn,
+    // remembers where the simulation left every Interpolated entity
+    Record,
+}
+
+// Resource, the game time owed to the simulation
+#[derive(Default)]
+pub struct SimulationClock {
+    accumulator: f32,
+}
+
+impl SimulationClock {
+    // how far the frame is between the last two steps
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / TIME_STEP).clamp(0.0, 1.0)
+    }
+
+    // most steps run in one frame, a long hitch slows the game down instead of freezing it
+    fn owe(&mut self, seconds: f32) {
+        self.accumulator = (self.accumulator + seconds).min(MAX_STEPS_PER_FRAME as f32 * TIME_STEP);
+    }
+}
+
+// Component, the simulated position of an entity, its Transform is drawn in between the last two
+#[derive(Component, Clone, Copy)]
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+}
+
+impl Interpolated {
+    pub fn at(translation: Vec3) -> Self {
+        Self {
+            previous: translation,
+            current: translation,
+        }
+    }
+}
+
+// movement systems go in here, once per step
+pub fn movement_systems() -> SystemSet {
+    SystemSet::new()
+        .label(SimulationSystem::Movement)
+        .after(SimulationSystem::Restore)
+        .before(SimulationSystem::Record)
+}
+
+// collision systems in here, after everything has moved
+pub fn collision_systems() -> SystemSet {
+    SystemSet::new()
+        .label(SimulationSystem::Collision)
+        .after(SimulationSystem::Movement)
+        .before(SimulationSystem::Record)
+}
+
+// and the systems reacting to collisions in here
+pub fn response_systems() -> SystemSet {
+    SystemSet::new()
+        .label(SimulationSystem::Response)
+        .after(SimulationSystem::Collision)
+        .before(SimulationSystem::Record)
+}
+
+// then whatever spawns or fires on a timer, ticked by TIME_STEP
+pub fn spawn_systems() -> SystemSet {
+    SystemSet::new()
+        .label(SimulationSystem::Spawn)
+        .after(SimulationSystem::Response)
+        .before(SimulationSystem::Record)
+}
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SimulationClock>()
+            .add_system_to_stage(CoreStage::First, advance_clock)
+            .add_stage_before(
+                CoreStage::Update,
+                SimulationStage,
+                SystemStage::parallel().with_run_criteria(simulation_step),
+            )
+            .add_system_to_stage(SimulationStage, restore.label(SimulationSystem::Restore))
+            .add_system_to_stage(SimulationStage, record.label(SimulationSystem::Record))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+// only time spent playing is owed, never the time spent paused
+fn advance_clock(
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    if *state.current() == AppState::Playing {
+        clock.owe(time.delta_seconds());
+    }
+}
+
+// a fixed timestep, as many steps as the clock is owed
+fn simulation_step(
+    state: Res<State<AppState>>,
+    mut clock: ResMut<SimulationClock>,
+) -> ShouldRun {
+    if *state.current() != AppState::Playing {
+        return ShouldRun::No;
+    }
+
+    if clock.accumulator >= TIME_STEP {
+        clock.accumulator -= TIME_STEP;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn restore(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut tf, mut interpolated) in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+        tf.translation = interpolated.current;
+    }
+}
+
+fn record(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (tf, mut interpolated) in query.iter_mut() {
+        interpolated.current = tf.translation;
+    }
+}
+
+fn interpolate(
+    clock: Res<SimulationClock>,
+    mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+    let alpha = clock.alpha();
+    for (mut tf, interpolated) in query.iter_mut() {
+        tf.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invaders::{
+        hitbox::{Hitbox, Hitboxes},
+        player::{player_fire, toggle_autofire, FireInput, FireSettings, Weapon},
+        FromPlayer, Player,
+    };
+
+    const SPEED: f32 = 100.0;
+
+    // Resource, the frame time the test plays at
+    struct FrameTime(f32);
+
+    fn feed_clock(frame_time: Res<FrameTime>, mut clock: ResMut<SimulationClock>) {
+        clock.owe(frame_time.0);
+    }
+
+    // fire held down all along, as the input latch would see it
+    fn hold_fire(mut input: ResMut<FireInput>) {
+        input.held = true;
+    }
+
+    fn drift(mut query: Query<&mut Transform, With<Interpolated>>) {
+        for mut tf in query.iter_mut() {
+            tf.translation.x += SPEED * TIME_STEP;
+        }
+    }
+
+    // plays `seconds` at `rate` frames per second with fire held, the player drifting along.
+    // Half a step more is played, so rounding never leaves one rate a step short of the others
+    fn play(rate: u32, seconds: f32, autofire: bool) -> (App, Entity) {
+        let frames = (rate as f32 * seconds).round() as u32;
+        let mut app = App::new();
+        app
+            .insert_resource(Time::default())
+            .insert_resource(State::new(AppState::Playing))
+            .insert_resource(FrameTime((seconds + TIME_STEP / 2.0) / frames as f32))
+            .insert_resource(FireSettings { autofire, ..Default::default() })
+            .init_resource::<FireInput>()
+            .insert_resource(Hitboxes {
+                player: Hitbox::default(),
+                player_laser: Hitbox::default(),
+                enemies: Default::default(),
+                enemy_laser: Hitbox::default(),
+                boss: Hitbox::default(),
+            })
+            .add_plugin(SimulationPlugin)
+            .add_system_to_stage(CoreStage::First, feed_clock)
+            .add_system_to_stage(CoreStage::First, hold_fire)
+            .add_system_set_to_stage(SimulationStage, movement_systems().with_system(drift))
+            // no collision systems here to put the spawn systems after the movement ones
+            .add_system_set_to_stage(
+                SimulationStage,
+                spawn_systems()
+                    .with_system(toggle_autofire)
+                    .with_system(player_fire.after(toggle_autofire).after(drift)),
+            );
+        let player = app
+            .world
+            .spawn()
+            .insert(Player)
+            .insert(Weapon::base(Handle::default()))
+            .insert(Transform::default())
+            .insert(Interpolated::at(Vec3::ZERO))
+            .id();
+
+        for _ in 0..frames {
+            app.update();
+        }
+        (app, player)
+    }
+
+    // where every player laser is drawn, left to right
+    fn lasers(app: &mut App) -> Vec<Vec3> {
+        let mut lasers: Vec<Vec3> = app
+            .world
+            .query_filtered::<&Transform, With<FromPlayer>>()
+            .iter(&app.world)
+            .map(|tf| tf.translation)
+            .collect();
+        lasers.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        lasers
+    }
+
+    #[test]
+    fn same_position_at_any_frame_rate() {
+        let (app, player) = play(60, 1.0, false);
+        let expected = app.world.get::<Transform>(player).unwrap().translation;
+        assert!(expected.x > 0.0);
+        for rate in [30, 144] {
+            let (app, player) = play(rate, 1.0, false);
+            let position = app.world.get::<Transform>(player).unwrap().translation;
+            assert!(
+                (position - expected).length() < 1e-3,
+                "at {} Hz the entity is at {}, at 60 Hz at {}",
+                rate,
+                position,
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn same_shots_at_any_frame_rate() {
+        let (mut app, _) = play(60, 1.0, true);
+        let expected = lasers(&mut app);
+        assert!(expected.len() > Weapon::BASE_PROJECTILES as usize);
+        for rate in [30, 144] {
+            let (mut app, _) = play(rate, 1.0, true);
+            let shots = lasers(&mut app);
+            assert_eq!(shots.len(), expected.len(), "at {} Hz", rate);
+            for (shot, expected) in shots.iter().zip(&expected) {
+                assert!(
+                    (*shot - *expected).length() < 1e-3,
+                    "at {} Hz a laser is at {}, at 60 Hz at {}",
+                    rate,
+                    shot,
+                    expected,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_charge_at_any_frame_rate() {
+        let (app, player) = play(60, 0.5, false);
+        let expected = app.world.get::<Weapon>(player).unwrap().charge();
+        assert!(expected > 0.0 && expected < 1.0);
+        for rate in [30, 144] {
+            let (app, player) = play(rate, 0.5, false);
+            let charge = app.world.get::<Weapon>(player).unwrap().charge();
+            assert!(
+                (charge - expected).abs() < 1e-3,
+                "at {} Hz the charge is {}, at 60 Hz {}",
+                rate,
+                charge,
+                expected,
+            );
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    ActiveEnemies, Wave, EnemyKind, TIME_STEP,
+    movement::MovementPattern,
+    simulation::{spawn_systems, SimulationStage},
+};
+
+pub const WAVES_FILE: &str = "waves/invaders.waves.ron";
+// shortest spawn or fire interval, however far the escalation goes
+const MIN_INTERVAL: f32 = 0.05;
+
+// region:      Waves asset
+#[derive(Debug, Clone, Deserialize)]
+pub struct WaveDefinition {
+    pub enemy_count: u32,
+    pub formation_size: u32,
+    // most enemies alive at once
+    pub max_on_screen: u32,
+    pub speed: f32,
+    pub spawn_interval: f32,
+    pub fire_interval: f32,
+    // each formation is one of these, picked at random
+    pub enemies: Vec<EnemyKind>,
+    // and flies one of these
+    pub patterns: Vec<MovementPattern>,
+    // boss waves are not written in the file, see Waves::boss_every
+    #[serde(skip)]
+    pub boss: bool,
+}
+
+// applied once more for every wave past the last defined one
+#[derive(Debug, Clone, Deserialize)]
+struct Escalation {
+    extra_enemies: u32,
+    speed_factor: f32,
+    fire_interval_factor: f32,
+}
+
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "0f8b7f43-6a2e-4b8c-9a3d-5f1e2c7d9b40"]
+pub struct Waves {
+    waves: Vec<WaveDefinition>,
+    escalation: Escalation,
+    // every n-th wave is a boss fight instead
+    boss_every: u32,
+}
+
+impl Waves {
+    // waves are numbered from 1
+    pub fn definition(&self, number: u32) -> Option<WaveDefinition> {
+        let last = self.waves.len() as u32;
+        if last == 0 || number == 0 {
+            return None;
+        }
+        let mut definition = if number <= last {
+            self.waves[(number - 1) as usize].clone()
+        } else {
+            // past the end: keep replaying the last wave, harder every time
+            let extra = number - last;
+            let mut definition = self.waves[(last - 1) as usize].clone();
+            definition.enemy_count += self.escalation.extra_enemies * extra;
+            definition.speed *= self.escalation.speed_factor.powi(extra as i32);
+            definition.fire_interval *= self.escalation.fire_interval_factor.powi(extra as i32);
+            definition
+        };
+        definition.spawn_interval = definition.spawn_interval.max(MIN_INTERVAL);
+        definition.fire_interval = definition.fire_interval.max(MIN_INTERVAL);
+
+        // the boss is the only enemy of its wave
+        if self.boss_every > 0 && number % self.boss_every == 0 {
+            definition.boss = true;
+            definition.enemy_count = 1;
+        }
+        Some(definition)
+    }
+
+    // what would break the game if it got through, the first problem found
+    fn validate(&self) -> Result<(), String> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        for (i, wave) in self.waves.iter().enumerate() {
+            let number = i + 1;
+            if !positive(wave.spawn_interval) {
+                return Err(format!("wave {} has spawn_interval {}, it must be above 0", number, wave.spawn_interval));
+            }
+            if !positive(wave.fire_interval) {
+                return Err(format!("wave {} has fire_interval {}, it must be above 0", number, wave.fire_interval));
+            }
+            if wave.max_on_screen == 0 {
+                return Err(format!("wave {} has max_on_screen 0, it must be above 0", number));
+            }
+            if wave.enemies.is_empty() {
+                return Err(format!("wave {} has no enemies", number));
+            }
+            if wave.patterns.is_empty() {
+                return Err(format!("wave {} has no patterns", number));
+            }
+        }
+        if !positive(self.escalation.speed_factor) {
+            return Err(format!("speed_factor is {}, it must be above 0", self.escalation.speed_factor));
+        }
+        if !positive(self.escalation.fire_interval_factor) {
+            return Err(format!("fire_interval_factor is {}, it must be above 0", self.escalation.fire_interval_factor));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct WavesLoader;
+
+impl AssetLoader for WavesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let waves: Waves = ron::de::from_bytes(bytes)?;
+            // a bad edit keeps the waves already loaded
+            if let Err(problem) = waves.validate() {
+                warn!("Rejecting {}: {}", load_context.path().display(), problem);
+                return Err(anyhow::anyhow!(problem));
+            }
+            load_context.set_default_asset(LoadedAsset::new(waves));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+// endregion:   Waves asset
+
+// Resource
+pub struct WavesHandle(pub Handle<Waves>);
+
+// Resource, the definition of the wave being fought (None until the waves file has loaded)
+#[derive(Default)]
+pub struct CurrentWave(pub Option<WaveDefinition>);
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<Waves>()
+            .init_asset_loader::<WavesLoader>()
+            .init_resource::<CurrentWave>()
+            .add_startup_system(setup)
+            .add_system_set_to_stage(SimulationStage, spawn_systems().with_system(advance_wave))
+            .add_system(current_wave);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(WavesHandle(asset_server.load(WAVES_FILE)));
+}
+
+pub fn advance_wave(
+    current_wave: Res<CurrentWave>,
+    active_enemies: Res<ActiveEnemies>,
+    mut wave: ResMut<Wave>,
+) {
+    if !wave.intermission.finished() {
+        wave.intermission.tick(Duration::from_secs_f32(TIME_STEP));
+        return;
+    }
+
+    // a wave is over once all of it has been spawned and shot down
+    if let Some(definition) = &current_wave.0 {
+        if wave.spawned >= definition.enemy_count && active_enemies.0 == 0 {
+            wave.next();
+        }
+    }
+}
+
+// follows the wave number and the waves file, which can be edited while the game runs
+fn current_wave(
+    wave: Res<Wave>,
+    waves_handle: Res<WavesHandle>,
+    waves: Res<Assets<Waves>>,
+    mut current_wave: ResMut<CurrentWave>,
+    mut number: Local<u32>,
+    mut events: EventReader<AssetEvent<Waves>>,
+) {
+    let reloaded = events.iter().count() > 0;
+    if *number == wave.number && !reloaded && current_wave.0.is_some() {
+        return;
+    }
+
+    current_wave.0 = waves
+        .get(&waves_handle.0)
+        .and_then(|waves| waves.definition(wave.number));
+    if current_wave.0.is_some() {
+        *number = wave.number;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_file_loads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/waves/invaders.waves.ron");
+        let waves: Waves = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(waves.validate(), Ok(()));
+
+        // every wave has a definition, the ones past the end of the file too
+        for number in 1..=waves.waves.len() as u32 + waves.boss_every {
+            assert!(waves.definition(number).is_some());
+        }
+    }
+
+    #[test]
+    fn max_on_screen_and_speed_factor_must_be_above_0() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/waves/invaders.waves.ron");
+        let mut waves: Waves = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        waves.waves[0].max_on_screen = 0;
+        assert!(waves.validate().is_err());
+
+        waves.waves[0].max_on_screen = 1;
+        for speed_factor in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            waves.escalation.speed_factor = speed_factor;
+            assert!(waves.validate().is_err(), "speed_factor {} got through", speed_factor);
+        }
+    }
+}
@@ -1,9 +1,12 @@
 mod invaders;
 mod game_of_life;
+mod input_map;
+mod config;
+mod widgets;
 
 use clap::{App, arg};
 
-use invaders::invaders_app;
+use invaders::{invaders_app, WindowSettings};
 use game_of_life::game_of_life_app;
 
 fn main() {
@@ -12,11 +15,35 @@ fn main() {
                             .author("Sindre Fjermestad")
                             .about("Bevy engine tutorials followed")
                             .arg(arg!(-g --game <GAME_NAME> "Specify which game tutorial to run.").required(true).ignore_case(true))
+                            .arg(arg!(-s --seed [SEED] "Seed for the random numbers of invaders, to replay a run.").required(false))
+                            .arg(arg!(--"window-size" [SIZE] "Invaders window size as WIDTHxHEIGHT, e.g. 800x900.").required(false))
+                            .arg(arg!(--"window-position" [POSITION] "Invaders window position as X,Y, e.g. 100,50.").required(false))
                             .get_matches();
     
     match matches.value_of("game").unwrap() {
-        "invaders" => invaders_app(),
+        "invaders" => {
+            let seed = matches.value_of("seed").map(|seed| {
+                seed.parse::<u64>().unwrap_or_else(|_| panic!("The seed must be a whole number, not {}", seed))
+            });
+            let window = WindowSettings {
+                size: matches.value_of("window-size").map(|size| {
+                    parse_pair(size, 'x')
+                        .filter(|&size| WindowSettings::valid_size(size))
+                        .unwrap_or_else(|| panic!("The window size must look like 800x900 and be above 0, not {}", size))
+                }),
+                position: matches.value_of("window-position").map(|position| {
+                    parse_pair(position, ',').unwrap_or_else(|| panic!("The window position must look like 100,50, not {}", position))
+                }),
+            };
+            invaders_app(seed, window)
+        }
         "gol" | "game-of-life" | "game_of_life"  => game_of_life_app(),
         _ => panic!("WHAT WAS THAT!?"),
     }
 }
+
+// "800x900" and the like
+fn parse_pair<T: std::str::FromStr>(value: &str, separator: char) -> Option<(T, T)> {
+    let (first, second) = value.split_once(separator)?;
+    Some((first.trim().parse().ok()?, second.trim().parse().ok()?))
+}
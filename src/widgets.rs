@@ -0,0 +1,623 @@
+use bevy::{
+    prelude::*,
+    ecs::system::EntityCommands,
+};
+
+use crate::input_map::KeyboardCapture;
+
+// region:      Actions and events
+// what a widget does, each game names its own, e.g. WidgetAction("gol.play_pause")
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetAction(pub &'static str);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WidgetValue {
+    Clicked,
+    Toggled(bool),
+    Number(f32),
+    Text(String),
+    Selected(usize),
+}
+
+// the one event every widget sends
+pub struct WidgetEvent {
+    pub action: WidgetAction,
+    pub value: WidgetValue,
+}
+// endregion:   Actions and events
+
+// region:      Resources
+// Resource, colours and font shared by all widgets, games overwrite it to theme them
+pub struct WidgetStyle {
+    pub font: Handle<Font>,
+    pub text_color: Color,
+    pub normal: Color,
+    pub hovered: Color,
+    pub pressed: Color,
+    pub active: Color,
+    pub track: Color,
+    pub button_image: Handle<Image>,
+}
+
+impl Default for WidgetStyle {
+    fn default() -> Self {
+        Self {
+            font: Default::default(),
+            text_color: Color::WHITE,
+            normal: Color::rgb(0.15, 0.15, 0.15),
+            hovered: Color::rgb(0.25, 0.25, 0.25),
+            pressed: Color::rgb(0.35, 0.75, 0.35),
+            active: Color::rgb(0.2, 0.45, 0.2),
+            track: Color::rgb(0.08, 0.08, 0.08),
+            button_image: UiImage::default().0,
+        }
+    }
+}
+
+// Resource
+#[derive(Default)]
+struct FocusedTextInput(Option<Entity>);
+// endregion:   Resources
+
+// region:      Components
+// clickable widget background, coloured by its interaction
+#[derive(Component)]
+struct WidgetBackground;
+
+// text child of a widget, showing its label and value
+#[derive(Component)]
+struct WidgetText;
+
+#[derive(Component)]
+pub struct Toggle {
+    pub on: bool,
+    pub off_label: String,
+    pub on_label: String,
+}
+
+#[derive(Component)]
+pub struct Slider {
+    pub label: String,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub value: f32,
+}
+
+impl Slider {
+    fn fraction(&self) -> f32 {
+        (self.value - self.min) / (self.max - self.min)
+    }
+
+    fn value_at(&self, fraction: f32) -> f32 {
+        let value = self.min + fraction.clamp(0.0, 1.0) * (self.max - self.min);
+        (value / self.step).round() * self.step
+    }
+}
+
+#[derive(Component)]
+struct SliderFill;
+
+#[derive(Component)]
+pub struct TextInput {
+    pub label: String,
+    pub value: String,
+}
+
+// text input that takes the focus as soon as it is spawned
+#[derive(Component)]
+pub struct Autofocus;
+
+#[derive(Component)]
+pub struct Dropdown {
+    pub label: String,
+    pub options: Vec<String>,
+    pub selected: usize,
+}
+
+#[derive(Component)]
+struct DropdownList {
+    dropdown: Entity,
+}
+
+#[derive(Component)]
+struct DropdownOption {
+    dropdown: Entity,
+    index: usize,
+}
+// endregion:   Components
+
+pub struct WidgetPlugin;
+
+impl Plugin for WidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<WidgetEvent>()
+            .init_resource::<WidgetStyle>()
+            .init_resource::<FocusedTextInput>()
+            .init_resource::<KeyboardCapture>()
+            .add_system(button_clicks)
+            .add_system(toggle_clicks)
+            .add_system(slider_drag)
+            .add_system(text_input_focus)
+            .add_system(text_input_typing.after(text_input_focus))
+            .add_system(dropdown_open)
+            .add_system(dropdown_select)
+            .add_system(widget_labels
+                .after(toggle_clicks)
+                .after(slider_drag)
+                .after(text_input_typing)
+                .after(dropdown_select))
+            .add_system(widget_colors.after(toggle_clicks))
+            .add_system(widget_text_style);
+    }
+}
+
+// region:      Builders
+// NB: the ui layout is flipped vertically, a "bottom" position is from the top of the screen
+// and FlexDirection::ColumnReverse stacks children from the top down
+// full width row, the usual size for widgets stacked in a panel
+pub fn row_style(height: f32) -> Style {
+    Style {
+        size: Size::new(Val::Percent(100.0), Val::Px(height)),
+        margin: Rect::all(Val::Px(4.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    }
+}
+
+// font and colour are filled in from the WidgetStyle
+fn build_widget_text(value: &str, font_size: f32) -> TextBundle {
+    TextBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font_size,
+                ..Default::default()
+            },
+            Default::default()
+        ),
+        ..Default::default()
+    }
+}
+
+pub fn spawn_label<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    value: &str,
+    font_size: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut label = parent.spawn_bundle(build_widget_text(value, font_size));
+    label.insert(WidgetText);
+    label
+}
+
+pub fn spawn_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    action: WidgetAction,
+    label: &str,
+    style: Style,
+    font_size: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn_bundle(ButtonBundle {
+        style,
+        ..Default::default()
+    });
+    button
+        .insert(action)
+        .insert(WidgetBackground)
+        .with_children(|parent| {
+            spawn_label(parent, label, font_size);
+        });
+    button
+}
+
+pub fn spawn_toggle<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    action: WidgetAction,
+    toggle: Toggle,
+    style: Style,
+    font_size: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let label = if toggle.on { &toggle.on_label } else { &toggle.off_label }.clone();
+    let mut button = spawn_button(parent, action, &label, style, font_size);
+    button.insert(toggle);
+    button
+}
+
+pub fn spawn_slider<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    action: WidgetAction,
+    slider: Slider,
+    style: Style,
+    font_size: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let fraction = slider.fraction();
+    let label = format!("{}: {}", slider.label, slider.value);
+    let mut track = parent.spawn_bundle(NodeBundle {
+        style,
+        ..Default::default()
+    });
+    track
+        .insert(action)
+        .insert(Interaction::default())
+        .insert(slider)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(fraction * 100.0), Val::Percent(100.0)),
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Px(0.0),
+                            top: Val::Px(0.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(SliderFill);
+            spawn_label(parent, &label, font_size);
+        });
+    track
+}
+
+pub fn spawn_text_input<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    action: WidgetAction,
+    text_input: TextInput,
+    style: Style,
+    font_size: f32,
+) -> EntityCommands<'w, 's, 'a> {
+    let label = format!("{}: {}", text_input.label, text_input.value);
+    let mut field = parent.spawn_bundle(NodeBundle {
+        style,
+        ..Default::default()
+    });
+    field
+        .insert(action)
+        .insert(Interaction::default())
+        .insert(text_input)
+        .with_children(|parent| {
+            spawn_label(parent, &label, font_size);
+        });
+    field
+}
+
+// the option list is spawned as the next sibling, so it pushes the widgets below it down when open
+pub fn spawn_dropdown(
+    parent: &mut ChildBuilder,
+    action: WidgetAction,
+    dropdown: Dropdown,
+    style: Style,
+    font_size: f32,
+) -> Entity {
+    let options = dropdown.options.clone();
+    let label = format!("{}: {} v", dropdown.label, dropdown.options[dropdown.selected]);
+    let dropdown = spawn_button(parent, action, &label, style.clone(), font_size)
+        .insert(dropdown)
+        .id();
+
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::ColumnReverse,
+                display: Display::None,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(DropdownList { dropdown })
+        .with_children(|parent| {
+            for (index, option) in options.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: style.clone(),
+                        ..Default::default()
+                    })
+                    .insert(WidgetBackground)
+                    .insert(DropdownOption { dropdown, index })
+                    .with_children(|parent| {
+                        spawn_label(parent, option, font_size);
+                    });
+            }
+        });
+
+    dropdown
+}
+// endregion:   Builders
+
+// region:      Behaviour
+fn button_clicks(
+    buttons: Query<(&Interaction, &WidgetAction), (Changed<Interaction>, With<Button>, Without<Toggle>, Without<Dropdown>)>,
+    mut widget_writer: EventWriter<WidgetEvent>,
+) {
+    for (interaction, action) in buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            widget_writer.send(WidgetEvent { action: *action, value: WidgetValue::Clicked });
+        }
+    }
+}
+
+fn toggle_clicks(
+    mut toggles: Query<(&Interaction, &WidgetAction, &mut Toggle), Changed<Interaction>>,
+    mut widget_writer: EventWriter<WidgetEvent>,
+) {
+    for (interaction, action, mut toggle) in toggles.iter_mut() {
+        if *interaction == Interaction::Clicked {
+            toggle.on = !toggle.on;
+            widget_writer.send(WidgetEvent { action: *action, value: WidgetValue::Toggled(toggle.on) });
+        }
+    }
+}
+
+fn slider_drag(
+    windows: Res<Windows>,
+    mut sliders: Query<(&Interaction, &Node, &GlobalTransform, &WidgetAction, &mut Slider)>,
+    mut widget_writer: EventWriter<WidgetEvent>,
+) {
+    let cursor = match windows.get_primary().and_then(|window| window.cursor_position()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // Clicked sticks while the button is held, so dragging outside the track keeps working
+    for (interaction, node, global_transform, action, mut slider) in sliders.iter_mut() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let left = global_transform.translation.x - node.size.x / 2.0;
+        let value = slider.value_at((cursor.x - left) / node.size.x);
+        if slider.value != value {
+            slider.value = value;
+            widget_writer.send(WidgetEvent { action: *action, value: WidgetValue::Number(value) });
+        }
+    }
+}
+
+fn text_input_focus(
+    mouse_input: Res<Input<MouseButton>>,
+    text_inputs: Query<(Entity, &Interaction), With<TextInput>>,
+    autofocused: Query<Entity, (With<TextInput>, Added<Autofocus>)>,
+    mut focused: ResMut<FocusedTextInput>,
+    mut keyboard_capture: ResMut<KeyboardCapture>,
+) {
+    if let Some(entity) = autofocused.iter().next() {
+        focused.0 = Some(entity);
+        keyboard_capture.0 = true;
+        return;
+    }
+
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    focused.0 = text_inputs
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Clicked)
+        .map(|(entity, _)| entity);
+    keyboard_capture.0 = focused.0.is_some();
+}
+
+fn text_input_typing(
+    mut characters: EventReader<ReceivedCharacter>,
+    mut focused: ResMut<FocusedTextInput>,
+    mut keyboard_capture: ResMut<KeyboardCapture>,
+    mut text_inputs: Query<(&WidgetAction, &mut TextInput)>,
+    mut widget_writer: EventWriter<WidgetEvent>,
+) {
+    let entity = match focused.0 {
+        Some(entity) => entity,
+        None => {
+            // drain the events, they are not meant for us
+            characters.iter().count();
+            return;
+        }
+    };
+    let (action, mut text_input) = match text_inputs.get_mut(entity) {
+        Ok(text_input) => text_input,
+        Err(_) => {
+            // the field was despawned while focused
+            focused.0 = None;
+            keyboard_capture.0 = false;
+            return;
+        }
+    };
+
+    for event in characters.iter() {
+        match event.char {
+            // backspace
+            '\u{8}' => {
+                text_input.value.pop();
+            }
+            // enter submits, escape just leaves the field
+            '\r' | '\n' => {
+                focused.0 = None;
+                keyboard_capture.0 = false;
+                widget_writer.send(WidgetEvent {
+                    action: *action,
+                    value: WidgetValue::Text(text_input.value.clone()),
+                });
+            }
+            '\u{1b}' => {
+                focused.0 = None;
+                keyboard_capture.0 = false;
+            }
+            c if !c.is_control() => text_input.value.push(c),
+            _ => {}
+        }
+    }
+}
+
+fn dropdown_open(
+    dropdowns: Query<(Entity, &Interaction), (Changed<Interaction>, With<Dropdown>)>,
+    mut lists: Query<(&DropdownList, &mut Style)>,
+) {
+    for (dropdown, interaction) in dropdowns.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        for (list, mut style) in lists.iter_mut() {
+            if list.dropdown == dropdown {
+                style.display = match style.display {
+                    Display::None => Display::Flex,
+                    Display::Flex => Display::None,
+                };
+            }
+        }
+    }
+}
+
+fn dropdown_select(
+    options: Query<(&Interaction, &DropdownOption), Changed<Interaction>>,
+    mut dropdowns: Query<(&WidgetAction, &mut Dropdown)>,
+    mut lists: Query<(&DropdownList, &mut Style)>,
+    mut widget_writer: EventWriter<WidgetEvent>,
+) {
+    for (interaction, option) in options.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if let Ok((action, mut dropdown)) = dropdowns.get_mut(option.dropdown) {
+            dropdown.selected = option.index;
+            widget_writer.send(WidgetEvent {
+                action: *action,
+                value: WidgetValue::Selected(option.index),
+            });
+        }
+        for (list, mut style) in lists.iter_mut() {
+            if list.dropdown == option.dropdown {
+                style.display = Display::None;
+            }
+        }
+    }
+}
+// endregion:   Behaviour
+
+// region:      Display
+fn widget_labels(
+    focused: Res<FocusedTextInput>,
+    toggles: Query<(&Toggle, &Children), Changed<Toggle>>,
+    sliders: Query<(&Slider, &Children), Changed<Slider>>,
+    text_inputs: Query<(Entity, &TextInput, &Children)>,
+    changed_text_inputs: Query<Entity, Changed<TextInput>>,
+    dropdowns: Query<(&Dropdown, &Children), Changed<Dropdown>>,
+    mut fills: Query<&mut Style, With<SliderFill>>,
+    mut texts: Query<&mut Text, With<WidgetText>>,
+) {
+    let mut set_label = |children: &Children, value: String| {
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = value.clone();
+            }
+        }
+    };
+
+    for (toggle, children) in toggles.iter() {
+        let label = if toggle.on { &toggle.on_label } else { &toggle.off_label };
+        set_label(children, label.clone());
+    }
+
+    for (slider, children) in sliders.iter() {
+        set_label(children, format!("{}: {}", slider.label, slider.value));
+        for &child in children.iter() {
+            if let Ok(mut style) = fills.get_mut(child) {
+                style.size.width = Val::Percent(slider.fraction() * 100.0);
+            }
+        }
+    }
+
+    for (entity, text_input, children) in text_inputs.iter() {
+        if !focused.is_changed() && changed_text_inputs.get(entity).is_err() {
+            continue;
+        }
+
+        let caret = if focused.0 == Some(entity) { "_" } else { "" };
+        set_label(children, format!("{}: {}{}", text_input.label, text_input.value, caret));
+    }
+
+    for (dropdown, children) in dropdowns.iter() {
+        set_label(children, format!("{}: {} v", dropdown.label, dropdown.options[dropdown.selected]));
+    }
+}
+
+fn background_color(style: &WidgetStyle, interaction: Interaction, active: bool) -> Color {
+    match interaction {
+        Interaction::Clicked => style.pressed,
+        Interaction::Hovered => style.hovered,
+        Interaction::None if active => style.active,
+        Interaction::None => style.normal,
+    }
+}
+
+fn widget_colors(
+    widget_style: Res<WidgetStyle>,
+    focused: Res<FocusedTextInput>,
+    mut backgrounds: Query<(&Interaction, &mut UiColor, &mut UiImage, Option<&Toggle>), With<WidgetBackground>>,
+    changed_backgrounds: Query<Entity, (With<WidgetBackground>, Or<(Changed<Interaction>, Changed<Toggle>, Added<WidgetBackground>)>)>,
+    mut text_inputs: Query<(Entity, &mut UiColor), (With<TextInput>, Without<WidgetBackground>)>,
+    mut tracks: Query<&mut UiColor, (With<Slider>, Without<WidgetBackground>, Without<TextInput>)>,
+    mut fills: Query<&mut UiColor, (With<SliderFill>, Without<Slider>, Without<WidgetBackground>, Without<TextInput>)>,
+    new_widgets: Query<(), Or<(Added<Slider>, Added<TextInput>)>>,
+) {
+    let restyle = widget_style.is_changed() || new_widgets.iter().next().is_some();
+
+    for entity in changed_backgrounds.iter() {
+        if let Ok((interaction, mut color, _, toggle)) = backgrounds.get_mut(entity) {
+            let active = toggle.map_or(false, |toggle| toggle.on);
+            color.0 = background_color(&widget_style, *interaction, active);
+        }
+    }
+
+    if restyle {
+        for (interaction, mut color, mut image, toggle) in backgrounds.iter_mut() {
+            let active = toggle.map_or(false, |toggle| toggle.on);
+            color.0 = background_color(&widget_style, *interaction, active);
+            image.0 = widget_style.button_image.clone();
+        }
+        for mut color in tracks.iter_mut() {
+            color.0 = widget_style.track;
+        }
+        for mut color in fills.iter_mut() {
+            color.0 = widget_style.normal;
+        }
+    }
+
+    if restyle || focused.is_changed() {
+        for (entity, mut color) in text_inputs.iter_mut() {
+            color.0 = if focused.0 == Some(entity) { widget_style.active } else { widget_style.track };
+        }
+    }
+}
+
+// new widgets pick up the current style, all of them when the style changes
+fn widget_text_style(
+    widget_style: Res<WidgetStyle>,
+    mut texts: Query<&mut Text, With<WidgetText>>,
+    new_texts: Query<Entity, Added<WidgetText>>,
+) {
+    let apply = |text: &mut Text| {
+        for section in text.sections.iter_mut() {
+            section.style.font = widget_style.font.clone();
+            section.style.color = widget_style.text_color;
+        }
+    };
+
+    if widget_style.is_changed() {
+        for mut text in texts.iter_mut() {
+            apply(&mut text);
+        }
+    } else {
+        for entity in new_texts.iter() {
+            if let Ok(mut text) = texts.get_mut(entity) {
+                apply(&mut text);
+            }
+        }
+    }
+}
+// endregion:   Display